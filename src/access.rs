@@ -0,0 +1,246 @@
+// Contrôle d'accès par adresse IP : règles allow/deny en CIDR (IPv4 et IPv6),
+// globales ou par opération, évaluées avant tout handler. Derrière un proxy
+// de confiance, l'adresse du client est lue dans X-Forwarded-For.
+use std::{env, net::IpAddr};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::Method,
+    middleware::Next,
+    Error, HttpRequest, HttpResponse,
+};
+use ipnet::IpNet;
+use tracing::warn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    // Consultation : page, listes, téléchargements
+    Download,
+    Upload,
+    Delete,
+    // Autres modifications : restauration, métadonnées, vérification
+    Edit,
+    // Pages d'administration (journal d'audit)
+    Admin,
+}
+
+impl Operation {
+    const ALL: [Operation; 5] = [Self::Download, Self::Upload, Self::Delete, Self::Edit, Self::Admin];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn env_suffix(self) -> &'static str {
+        match self {
+            Self::Download => "DOWNLOAD",
+            Self::Upload => "UPLOAD",
+            Self::Delete => "DELETE",
+            Self::Edit => "EDIT",
+            Self::Admin => "ADMIN",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Download => "consultation",
+            Self::Upload => "upload",
+            Self::Delete => "suppression",
+            Self::Edit => "modification",
+            Self::Admin => "administration",
+        }
+    }
+
+    fn classify(method: &Method, path: &str) -> Self {
+        if path.trim_start_matches('/').split('/').next() == Some("audit") {
+            return Self::Admin;
+        }
+        if method == Method::GET || method == Method::HEAD {
+            return Self::Download;
+        }
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            ["upload"] | ["secure"] => Self::Upload,
+            ["delete", _] | ["trash", _, "purge"] | ["trash", "empty"] | ["secure", _, "delete"] => Self::Delete,
+            _ => Self::Edit,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct Rules {
+    // Vide = tout le monde
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl Rules {
+    fn from_env(suffix: &str) -> Self {
+        let networks = |var: String| env::var(var).map(|v| parse_networks(&v)).unwrap_or_default();
+        Self {
+            allow: networks(format!("ALLOW_IPS{}", suffix)),
+            deny: networks(format!("DENY_IPS{}", suffix)),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    fn describe(&self) -> String {
+        let list = |networks: &[IpNet]| networks.iter().map(IpNet::to_string).collect::<Vec<_>>().join(" ");
+        let mut parts = Vec::new();
+        if !self.allow.is_empty() {
+            parts.push(format!("✓ {}", list(&self.allow)));
+        }
+        if !self.deny.is_empty() {
+            parts.push(format!("✗ {}", list(&self.deny)));
+        }
+        parts.join(" ")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessConfig {
+    global: Rules,
+    // Règles propres à une opération, qui remplacent la liste globale d'autorisation
+    operations: [Rules; 5],
+    // Proxys dont on accepte l'en-tête X-Forwarded-For
+    trusted_proxies: Vec<IpNet>,
+}
+
+impl AccessConfig {
+    pub fn from_env() -> Self {
+        Self {
+            global: Rules::from_env(""),
+            operations: Operation::ALL.map(|op| {
+                let mut rules = Rules::from_env(&format!("_{}", op.env_suffix()));
+                // Administration réservée à la machine locale, sauf règle explicite
+                if op == Operation::Admin && env::var("ALLOW_IPS_ADMIN").is_err() {
+                    rules.allow = parse_networks("127.0.0.0/8, ::1");
+                }
+                rules
+            }),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .map(|v| parse_networks(&v))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut rules = Vec::new();
+        if !self.global.is_empty() {
+            rules.push(self.global.describe());
+        }
+        for op in Operation::ALL {
+            let op_rules = &self.operations[op.index()];
+            if !op_rules.is_empty() {
+                rules.push(format!("{}: {}", op.label(), op_rules.describe()));
+            }
+        }
+        if rules.is_empty() {
+            rules.push("ouvert à tous".to_string());
+        }
+        if !self.trusted_proxies.is_empty() {
+            rules.push(format!("{} proxy(s) de confiance", self.trusted_proxies.len()));
+        }
+        rules.join(", ")
+    }
+
+    // Une adresse refusée globalement ou pour l'opération l'est toujours ;
+    // sinon elle doit figurer dans la liste d'autorisation de l'opération,
+    // à défaut dans la liste globale, si l'une d'elles est définie
+    fn allows(&self, ip: Option<IpAddr>, operation: Operation) -> bool {
+        let op_rules = &self.operations[operation.index()];
+        let matches = |networks: &[IpNet]| ip.is_some_and(|ip| networks.iter().any(|net| net.contains(&ip)));
+        if matches(&self.global.deny) || matches(&op_rules.deny) {
+            return false;
+        }
+        let allow = if op_rules.allow.is_empty() { &self.global.allow } else { &op_rules.allow };
+        allow.is_empty() || matches(allow)
+    }
+
+    // Adresse du client : celle de la connexion, ou, si elle vient d'un proxy
+    // de confiance, la dernière adresse de X-Forwarded-For qui n'en est pas un
+    fn client_ip(&self, req: &HttpRequest) -> Option<IpAddr> {
+        let peer = req.peer_addr().map(|addr| addr.ip())?;
+        let is_proxy = |ip: &IpAddr| self.trusted_proxies.iter().any(|net| net.contains(ip));
+        if !is_proxy(&peer) {
+            return Some(peer);
+        }
+        let forwarded: Vec<&str> = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .collect();
+        let mut client = peer;
+        for entry in forwarded.iter().rev() {
+            // Une entrée illisible arrête la remontée : ce qui précède n'est pas fiable
+            let Some(ip) = parse_forwarded(entry) else {
+                break;
+            };
+            client = ip;
+            if !is_proxy(&ip) {
+                break;
+            }
+        }
+        Some(client)
+    }
+}
+
+// Entrée de X-Forwarded-For : adresse nue, "[v6]:port" ou "v4:port"
+fn parse_forwarded(entry: &str) -> Option<IpAddr> {
+    if let Ok(ip) = entry.parse() {
+        return Some(ip);
+    }
+    if let Some(rest) = entry.strip_prefix('[') {
+        return rest.split_once(']').and_then(|(ip, _)| ip.parse().ok());
+    }
+    entry.rsplit_once(':').and_then(|(ip, _)| ip.parse::<std::net::Ipv4Addr>().ok()).map(IpAddr::V4)
+}
+
+// Liste d'adresses ou de réseaux CIDR séparés par des virgules
+pub fn parse_networks(value: &str) -> Vec<IpNet> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let network = entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from));
+            if network.is_err() {
+                warn!(entry, "⚠️ Adresse ou réseau invalide ignoré");
+            }
+            network.ok()
+        })
+        .collect()
+}
+
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    AccessConfig::from_env().client_ip(req)
+}
+
+pub fn client_address(req: &HttpRequest) -> String {
+    client_ip(req).map_or("inconnu".to_string(), |ip| ip.to_string())
+}
+
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let config = AccessConfig::from_env();
+    let ip = config.client_ip(req.request());
+    let operation = Operation::classify(req.method(), req.path());
+    if !config.allows(ip, operation) {
+        warn!(operation = operation.label(), "⛔ Accès refusé");
+        let response = HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Accès refusé depuis cette adresse"
+        }));
+        return Ok(req.into_response(response).map_into_right_body());
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
@@ -0,0 +1,455 @@
+// Journal d'audit des opérations sur les fichiers : une ligne JSON par
+// événement, ajoutée à .audit/audit.log, rotation par taille. Consultable
+// (et filtrable) sur /audit, réservé par défaut à la machine du serveur.
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{get, http::header, web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::{
+    access, accepts_json, escape_html, format_file_size, format_timestamp,
+    retention::parse_ttl,
+    Config,
+};
+
+const AUDIT_DIR: &str = ".audit";
+const AUDIT_FILE: &str = "audit.log";
+const DEFAULT_LIMIT: usize = 200;
+const MAX_LIMIT: usize = 5000;
+// Auteur des opérations automatiques (expiration, purge de la corbeille)
+pub const SERVER: &str = "serveur";
+
+// Écritures et rotations d'un seul processus à la fois
+static WRITE: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone)]
+pub struct AuditConfig {
+    pub enabled: bool,
+    // Taille à partir de laquelle le journal est archivé
+    max_size: u64,
+    // Nombre d'archives conservées (audit.log.1, .2...)
+    keep: usize,
+}
+
+impl AuditConfig {
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env::var("AUDIT_LOG")
+                .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            max_size: env::var("AUDIT_MAX_SIZE")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|&mb| mb > 0)
+                .unwrap_or(10)
+                * 1024
+                * 1024,
+            keep: env::var("AUDIT_KEEP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return "désactivé".to_string();
+        }
+        format!("{}/{}, {} archive(s)", AUDIT_DIR, AUDIT_FILE, self.keep)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Event {
+    Upload,
+    Download,
+    Delete,
+    Rename,
+    Restore,
+    Share,
+}
+
+impl Event {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Upload => "📤 upload",
+            Self::Download => "📥 téléchargement",
+            Self::Delete => "🗑️ suppression",
+            Self::Rename => "✏️ renommage",
+            Self::Restore => "♻️ restauration",
+            Self::Share => "🔒 partage",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    // Date de l'événement (timestamp unix)
+    pub ts: u64,
+    pub event: Event,
+    pub ip: String,
+    // Utilisateur authentifié ; le serveur n'a pas encore de comptes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    pub file: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    // "ok", sinon la raison de l'échec
+    pub result: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Entry {
+    pub fn new(event: Event, ip: &str, file: &str) -> Self {
+        Self {
+            ts: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+            event,
+            ip: ip.to_string(),
+            user: None,
+            file: file.to_string(),
+            id: None,
+            size: None,
+            result: "ok".to_string(),
+            detail: None,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    pub fn failed(mut self, reason: &str) -> Self {
+        self.result = reason.to_string();
+        self
+    }
+
+    pub fn detail(mut self, detail: &str) -> Self {
+        self.detail = Some(detail.to_string());
+        self
+    }
+}
+
+fn audit_dir(upload_dir: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(AUDIT_DIR)
+}
+
+fn archive_path(upload_dir: &str, index: usize) -> PathBuf {
+    match index {
+        0 => audit_dir(upload_dir).join(AUDIT_FILE),
+        n => audit_dir(upload_dir).join(format!("{}.{}", AUDIT_FILE, n)),
+    }
+}
+
+// Ajoute un événement au journal ; une erreur d'écriture n'interrompt jamais l'opération
+pub fn record(upload_dir: &str, entry: Entry) {
+    let config = AuditConfig::from_env();
+    if !config.enabled {
+        return;
+    }
+    let _guard = WRITE.lock().unwrap();
+    let result = serde_json::to_string(&entry)
+        .map_err(std::io::Error::other)
+        .and_then(|line| {
+            fs::create_dir_all(audit_dir(upload_dir))?;
+            rotate(upload_dir, &config)?;
+            let mut f = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(archive_path(upload_dir, 0))?;
+            writeln!(f, "{}", line)
+        });
+    if let Err(e) = result {
+        error!(error = %e, "❌ Erreur écriture journal d'audit");
+    }
+}
+
+// audit.log devient audit.log.1, chaque archive recule d'un rang, la plus ancienne disparaît
+fn rotate(upload_dir: &str, config: &AuditConfig) -> std::io::Result<()> {
+    let current = archive_path(upload_dir, 0);
+    if fs::metadata(&current).map_or(true, |m| m.len() < config.max_size) {
+        return Ok(());
+    }
+    if config.keep == 0 {
+        return fs::remove_file(&current);
+    }
+    let _ = fs::remove_file(archive_path(upload_dir, config.keep));
+    for index in (0..config.keep).rev() {
+        let from = archive_path(upload_dir, index);
+        if from.exists() {
+            fs::rename(&from, archive_path(upload_dir, index + 1))?;
+        }
+    }
+    Ok(())
+}
+
+// Réponse qui transmet le contenu depuis le début : 200, ou plage "bytes 0-"
+// (les reprises et les 304 ne comptent pas comme un nouveau téléchargement)
+pub fn full_transfer(response: &HttpResponse) -> bool {
+    let first_range = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|range| range.starts_with("bytes 0-"));
+    response.status().is_success() && (response.status().as_u16() == 200 || first_range)
+}
+
+// Téléchargement effectivement servi : complet, ou début d'une reprise
+// (les requêtes de plage suivantes d'un lecteur vidéo ne sont pas journalisées)
+pub fn download(req: &HttpRequest, upload_dir: &str, id: &str, name: &str, response: &HttpResponse) {
+    if !full_transfer(response) {
+        return;
+    }
+    let headers = response.headers();
+    let size = headers
+        .get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|range| range.rsplit('/').next())
+        .or_else(|| headers.get(header::CONTENT_LENGTH).and_then(|v| v.to_str().ok()))
+        .and_then(|size| size.parse().ok());
+    let mut entry = Entry::new(Event::Download, &access::client_address(req), name).id(id);
+    entry.size = size;
+    record(upload_dir, entry);
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AuditQuery {
+    event: Option<String>,
+    ip: Option<String>,
+    // Partie du nom de fichier, sans tenir compte de la casse
+    file: Option<String>,
+    // "ok" ou "failed"
+    result: Option<String>,
+    // Timestamp unix, ou durée écoulée ("24h", "7d")
+    since: Option<String>,
+    until: Option<String>,
+    limit: Option<usize>,
+}
+
+impl AuditQuery {
+    fn matches(&self, entry: &Entry, since: Option<u64>, until: Option<u64>) -> bool {
+        filled(&self.event).is_none_or(|event| serde_json::to_value(entry.event).is_ok_and(|v| v == event))
+            && filled(&self.ip).is_none_or(|ip| entry.ip.starts_with(ip))
+            && filled(&self.file).is_none_or(|file| entry.file.to_lowercase().contains(&file.to_lowercase()))
+            && filled(&self.result).is_none_or(|result| match result {
+                "ok" => entry.result == "ok",
+                "failed" => entry.result != "ok",
+                other => entry.result == other,
+            })
+            && since.is_none_or(|since| entry.ts >= since)
+            && until.is_none_or(|until| entry.ts <= until)
+    }
+}
+
+// Critère renseigné dans le formulaire (un champ vide ne filtre rien)
+fn filled(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn parse_time(value: Option<&str>, now: u64) -> Option<u64> {
+    let value = value?.trim();
+    match value.parse::<u64>() {
+        Ok(ts) if ts > 1_000_000_000 => Some(ts),
+        _ => parse_ttl(value).map(|ago| now.saturating_sub(ago)),
+    }
+}
+
+// Événements du plus récent au plus ancien, archives comprises
+fn search(upload_dir: &str, query: &AuditQuery) -> Vec<Entry> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let since = parse_time(query.since.as_deref(), now);
+    let until = parse_time(query.until.as_deref(), now);
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let keep = AuditConfig::from_env().keep;
+
+    let mut found = Vec::new();
+    for index in 0..=keep {
+        let Ok(file) = fs::File::open(archive_path(upload_dir, index)) else {
+            continue;
+        };
+        let mut entries: Vec<Entry> = BufReader::new(file)
+            .lines()
+            .map_while(|line| line.ok())
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .filter(|entry| query.matches(entry, since, until))
+            .collect();
+        entries.reverse();
+        found.extend(entries.into_iter().take(limit - found.len()));
+        if found.len() >= limit {
+            break;
+        }
+    }
+    found
+}
+
+#[get("/audit")]
+async fn view(req: HttpRequest) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let query = web::Query::<AuditQuery>::from_query(req.query_string())
+        .map(|query| query.into_inner())
+        .unwrap_or_default();
+    let upload_dir = config.upload_dir.clone();
+    let (query, entries) = web::block(move || {
+        let entries = search(&upload_dir, &query);
+        (query, entries)
+    })
+    .await?;
+
+    if accepts_json(&req) {
+        return Ok(HttpResponse::Ok()
+            .insert_header((header::CACHE_CONTROL, "no-store"))
+            .json(serde_json::json!({ "entries": entries })));
+    }
+
+    let rows: String = entries
+        .iter()
+        .map(|entry| {
+            format!(
+                "<tr class=\"{}\"><td title=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                if entry.result == "ok" { "ok" } else { "failed" },
+                httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(entry.ts)),
+                format_timestamp(entry.ts),
+                entry.event.label(),
+                escape_html(&entry.ip),
+                escape_html(&entry.file),
+                entry.size.map_or(String::new(), format_file_size),
+                escape_html(&entry.result),
+                escape_html(entry.detail.as_deref().unwrap_or_default()),
+            )
+        })
+        .collect();
+    let value = |field: &Option<String>| escape_html(field.as_deref().unwrap_or_default());
+    let events: String = ["upload", "download", "delete", "rename", "restore", "share"]
+        .iter()
+        .map(|event| {
+            let selected = if query.event.as_deref() == Some(*event) { " selected" } else { "" };
+            format!("<option value=\"{0}\"{1}>{0}</option>", event, selected)
+        })
+        .collect();
+
+    let html = format!(r#"
+    <!DOCTYPE html>
+    <html lang="fr">
+    <head>
+        <meta charset="UTF-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1.0">
+        <title>Serveur de partage - Journal d'audit</title>
+        <style>
+            body {{
+                font-family: 'Segoe UI', Tahoma, Geneva, Verdana, sans-serif;
+                background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+                color: white;
+                min-height: 100vh;
+                margin: 0;
+                padding: 20px;
+                box-sizing: border-box;
+            }}
+
+            .container {{
+                max-width: 1200px;
+                margin: 0 auto;
+                background: rgba(255, 255, 255, 0.1);
+                border-radius: 20px;
+                padding: 30px;
+                box-shadow: 0 8px 32px rgba(0, 0, 0, 0.3);
+            }}
+
+            form {{
+                display: flex;
+                flex-wrap: wrap;
+                gap: 10px;
+                margin-bottom: 20px;
+            }}
+
+            input, select, button {{
+                padding: 8px 12px;
+                border-radius: 10px;
+                border: none;
+            }}
+
+            button {{
+                background: linear-gradient(45deg, #4ecdc4, #44a08d);
+                color: white;
+                font-weight: bold;
+                cursor: pointer;
+            }}
+
+            table {{
+                width: 100%;
+                border-collapse: collapse;
+                font-size: 0.9em;
+            }}
+
+            th, td {{
+                text-align: left;
+                padding: 8px;
+                border-bottom: 1px solid rgba(255, 255, 255, 0.2);
+                word-break: break-all;
+            }}
+
+            tr.failed {{
+                background: rgba(255, 107, 107, 0.3);
+            }}
+        </style>
+    </head>
+    <body>
+        <div class="container">
+            <h1>📜 Journal d'audit</h1>
+            <form method="get" action="/audit">
+                <select name="event"><option value="">Tous les événements</option>{}</select>
+                <input name="file" placeholder="Nom de fichier" value="{}">
+                <input name="ip" placeholder="Adresse IP" value="{}">
+                <input name="since" placeholder="Depuis (24h, 7d...)" value="{}">
+                <select name="result">
+                    <option value="">Tous les résultats</option>
+                    <option value="ok"{}>Réussis</option>
+                    <option value="failed"{}>Échoués</option>
+                </select>
+                <button type="submit">🔎 Filtrer</button>
+            </form>
+            <p>{} événement(s) affiché(s)</p>
+            <table>
+                <tr><th>Date</th><th>Événement</th><th>Client</th><th>Fichier</th><th>Taille</th><th>Résultat</th><th>Détail</th></tr>
+                {}
+            </table>
+        </div>
+    </body>
+    </html>
+    "#,
+        events,
+        value(&query.file),
+        value(&query.ip),
+        value(&query.since),
+        if query.result.as_deref() == Some("ok") { " selected" } else { "" },
+        if query.result.as_deref() == Some("failed") { " selected" } else { "" },
+        entries.len(),
+        rows,
+    );
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(html))
+}
@@ -0,0 +1,136 @@
+// Requêtes conditionnelles : validateurs (ETag, Last-Modified) et réponses 304,
+// pour que navigateurs et proxys ne retéléchargent pas un contenu inchangé
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        LazyLock,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{http::header, HttpRequest, HttpResponse, HttpResponseBuilder};
+
+// Toujours revalider : le contenu d'un identifiant peut être remplacé
+pub const REVALIDATE: &str = "no-cache";
+// Contenu qui ne change jamais (versions archivées)
+pub const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+// Génération de l'index : avance à chaque changement affiché par la page
+// d'accueil (fichiers, corbeille, analyses, partages chiffrés)
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+// Démarrage du serveur : la génération repart de zéro à chaque lancement
+static STARTED: LazyLock<u64> = LazyLock::new(|| {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64
+});
+
+// À appeler une fois le changement écrit, jamais avant : une page rendue
+// entre les deux serait mise en cache sous la nouvelle génération
+pub fn invalidate_index() {
+    GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+#[derive(Debug, Clone)]
+pub struct Validators {
+    // ETag, guillemets compris (W/ s'il est faible)
+    pub etag: String,
+    // Date de dernière modification (timestamp unix)
+    pub last_modified: Option<u64>,
+}
+
+impl Validators {
+    // ETag tiré de l'empreinte du contenu, sinon de sa taille et de sa date
+    pub fn for_content(sha256: Option<&str>, size: u64, modified: u64) -> Self {
+        let etag = match sha256 {
+            Some(sha256) => format!("\"{}\"", sha256),
+            None => format!("\"{:x}-{:x}\"", size, modified),
+        };
+        Self {
+            etag,
+            last_modified: Some(modified).filter(|&modified| modified > 0),
+        }
+    }
+
+    // ETag de la page d'accueil, connu avant le rendu : un 304 évite de lister
+    // le stockage. Les durées relatives ("il y a 5 min") ne sont rafraîchies
+    // qu'au prochain changement. Faible, la page étant compressée selon le client.
+    pub fn for_index() -> Self {
+        Self {
+            etag: format!("W/\"{:x}-{:x}\"", *STARTED, GENERATION.load(Ordering::Relaxed)),
+            last_modified: None,
+        }
+    }
+
+    // Contenu compressé à la volée : mêmes données, octets différents
+    pub fn weak(self) -> Self {
+        if self.etag.starts_with("W/") {
+            return self;
+        }
+        Self {
+            etag: format!("W/{}", self.etag),
+            ..self
+        }
+    }
+
+    pub fn apply(&self, response: &mut HttpResponseBuilder) {
+        response.insert_header((header::ETAG, self.etag.as_str()));
+        if let Some(modified) = self.last_modified {
+            response.insert_header((header::LAST_MODIFIED, http_date(modified)));
+        }
+    }
+
+    // If-None-Match l'emporte sur If-Modified-Since quand les deux sont présents
+    pub fn not_modified(&self, req: &HttpRequest) -> bool {
+        if let Some(value) = header_str(req, header::IF_NONE_MATCH) {
+            return value.trim() == "*" || value.split(',').any(|tag| weak_eq(tag, &self.etag));
+        }
+        match (header_str(req, header::IF_MODIFIED_SINCE), self.last_modified) {
+            (Some(since), Some(modified)) => parse_http_date(since).is_some_and(|since| modified <= since),
+            _ => false,
+        }
+    }
+
+    // If-Range : la plage n'est servie que si le contenu n'a pas changé depuis
+    pub fn range_applies(&self, req: &HttpRequest) -> bool {
+        let Some(value) = header_str(req, header::IF_RANGE) else {
+            return true;
+        };
+        let value = value.trim();
+        if value.starts_with('"') {
+            // Comparaison forte : un ETag faible ne valide jamais une plage
+            return value == self.etag;
+        }
+        match (parse_http_date(value), self.last_modified) {
+            (Some(date), Some(modified)) => date == modified,
+            _ => false,
+        }
+    }
+
+    pub fn not_modified_response(&self, cache_control: &str) -> HttpResponse {
+        let mut response = HttpResponse::NotModified();
+        self.apply(&mut response);
+        response
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish()
+    }
+}
+
+fn header_str(req: &HttpRequest, name: header::HeaderName) -> Option<&str> {
+    req.headers().get(name).and_then(|v| v.to_str().ok())
+}
+
+// Comparaison faible (If-None-Match) : W/"x" équivaut à "x"
+fn weak_eq(tag: &str, etag: &str) -> bool {
+    let tag = tag.trim();
+    tag.strip_prefix("W/").unwrap_or(tag) == etag.strip_prefix("W/").unwrap_or(etag)
+}
+
+fn http_date(timestamp: u64) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(timestamp))
+}
+
+fn parse_http_date(value: &str) -> Option<u64> {
+    httpdate::parse_http_date(value.trim())
+        .ok()
+        .and_then(|date| date.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+}
@@ -0,0 +1,153 @@
+// Compression des réponses : négociée par le middleware Compress d'actix
+// (br, zstd, gzip), sauf pour les contenus déjà compressés. Un fichier partagé
+// accompagné de sa variante précompressée (<nom>.br, <nom>.gz) est servi tel quel.
+use std::env;
+
+use actix_web::{
+    http::header::{self, AcceptEncoding, ContentEncoding, Encoding, HeaderValue},
+    HttpMessage, HttpRequest, HttpResponse,
+};
+
+use crate::{meta::{self, FileMeta}, names, storage};
+
+// Encodages appliqués à la volée, dans l'ordre de préférence du serveur
+const DYNAMIC: &[Encoding] = &[Encoding::brotli(), Encoding::zstd(), Encoding::gzip(), Encoding::identity()];
+// Variantes précompressées recherchées à côté d'un fichier
+const PRECOMPRESSED: &[(Encoding, &str)] = &[(Encoding::brotli(), "br"), (Encoding::gzip(), "gz")];
+
+#[derive(Debug, Clone)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+}
+
+impl CompressionConfig {
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env::var("COMPRESSION")
+                .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if self.enabled {
+            "br, zstd, gzip (+ variantes .br/.gz)".to_string()
+        } else {
+            "désactivée".to_string()
+        }
+    }
+}
+
+// Types qui gagnent à être compressés : texte et formats structurés.
+// Images, vidéos, sons, archives et documents bureautiques le sont déjà.
+pub fn compressible(mime: &str) -> bool {
+    let mime = mime.split(';').next().unwrap_or_default().trim().to_lowercase();
+    let Some((kind, subtype)) = mime.split_once('/') else {
+        return false;
+    };
+    match kind {
+        "text" => true,
+        "image" => matches!(subtype, "svg+xml" | "bmp" | "x-icon" | "vnd.microsoft.icon"),
+        "font" => matches!(subtype, "ttf" | "otf"),
+        "application" => {
+            subtype.ends_with("+json")
+                || subtype.ends_with("+xml")
+                || matches!(
+                    subtype,
+                    "json" | "xml" | "javascript" | "ecmascript" | "x-javascript" | "x-ndjson"
+                        | "yaml" | "x-yaml" | "toml" | "sql" | "graphql" | "wasm" | "rtf"
+                        | "x-sh" | "x-csh" | "x-httpd-php" | "x-tex" | "postscript"
+                )
+        }
+        _ => false,
+    }
+}
+
+// Le client accepte-t-il une réponse compressée à la volée ?
+pub fn accepted(req: &HttpRequest) -> bool {
+    CompressionConfig::from_env().enabled
+        && req
+            .get_header::<AcceptEncoding>()
+            .and_then(|accept| accept.negotiate(DYNAMIC.iter()))
+            .is_some_and(|encoding| encoding != Encoding::identity())
+}
+
+// Réponse à laisser telle quelle (contenu déjà compressé, plage d'octets)
+pub fn skip(response: &mut HttpResponse) {
+    response
+        .headers_mut()
+        .insert(header::CONTENT_ENCODING, ContentEncoding::Identity.to_header_value());
+}
+
+pub struct Variant {
+    // Identifiant de stockage de la variante
+    pub id: String,
+    pub encoding: ContentEncoding,
+    pub meta: FileMeta,
+    // Type du fichier d'origine
+    mime: Option<String>,
+}
+
+// Variantes précompressées acceptées par le client, par ordre de préférence.
+// Jamais pour une reprise (Range) : les octets demandés sont ceux de l'original.
+pub fn accepted_variants(req: &HttpRequest) -> Vec<(ContentEncoding, &'static str)> {
+    let mut variants = Vec::new();
+    if !CompressionConfig::from_env().enabled || req.headers().contains_key(header::RANGE) {
+        return variants;
+    }
+    let Some(accept) = req.get_header::<AcceptEncoding>() else {
+        return variants;
+    };
+    let mut candidates: Vec<Encoding> = PRECOMPRESSED.iter().map(|(encoding, _)| encoding.clone()).collect();
+    while let Some(encoding) = accept.negotiate(candidates.iter()) {
+        candidates.retain(|candidate| *candidate != encoding);
+        match (PRECOMPRESSED.iter().find(|(e, _)| *e == encoding), encoding) {
+            (Some((_, extension)), Encoding::Known(content_encoding)) => variants.push((content_encoding, *extension)),
+            _ => break,
+        }
+    }
+    variants
+}
+
+// Première variante (<nom>.br, <nom>.gz) présente et au moins aussi récente que l'original
+pub fn precompressed(upload_dir: &str, id: &str, name: &str, original: &FileMeta, accepted: &[(ContentEncoding, &str)]) -> Option<Variant> {
+    // Noms résolus par l'index : le stockage n'est consulté que si une variante existe
+    let candidates: Vec<(ContentEncoding, String)> = accepted
+        .iter()
+        .filter_map(|(encoding, extension)| Some((*encoding, names::find(upload_dir, &format!("{}.{}", name, extension))?)))
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+    let storage = storage::open(upload_dir);
+    let modified = storage.stat(id).ok()??.modified;
+    candidates.into_iter().find_map(|(encoding, variant_id)| {
+        storage
+            .stat(&variant_id)
+            .ok()
+            .flatten()
+            .filter(|info| info.modified >= modified)?;
+        Some(Variant {
+            meta: meta::load(upload_dir, &variant_id),
+            id: variant_id,
+            encoding,
+            mime: original.mime.clone(),
+        })
+    })
+}
+
+impl Variant {
+    // Encodage et type d'origine de la variante servie (une 304 n'a pas de contenu)
+    pub fn apply(&self, response: &mut HttpResponse) {
+        let success = response.status().is_success();
+        let mime = self.mime.as_deref().unwrap_or("application/octet-stream");
+        let headers = response.headers_mut();
+        if success {
+            headers.insert(header::CONTENT_ENCODING, self.encoding.to_header_value());
+            if let Ok(value) = HeaderValue::from_str(mime) {
+                headers.insert(header::CONTENT_TYPE, value);
+            }
+        }
+        headers.insert(header::VARY, HeaderValue::from_static("Accept-Encoding"));
+    }
+}
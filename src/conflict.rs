@@ -0,0 +1,175 @@
+// Stratégies de résolution quand un fichier du même nom existe déjà
+use std::{
+    env,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{meta, names, storage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    // "nom_<timestamp>.ext" (comportement historique)
+    Timestamp,
+    // Remplacer le fichier existant
+    Overwrite,
+    // Refuser l'upload (409)
+    Reject,
+    // "nom (1).ext", "nom (2).ext"...
+    Number,
+    // Garder les deux : "nom-<hash>.ext", un contenu identique n'est pas dupliqué
+    Hash,
+    // Le fichier existant devient une version précédente
+    Version,
+}
+
+// Emplacement final décidé pour un upload
+pub enum Target {
+    // Nouveau fichier, sous ce nom (éventuellement renommé)
+    New(String),
+    // Le fichier existant (identifiant) est remplacé
+    Replace(String),
+    // Le même contenu existe déjà sous ce nom
+    Duplicate { id: String, name: String },
+}
+
+impl ConflictPolicy {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "timestamp" | "rename" => Some(Self::Timestamp),
+            "overwrite" | "replace" => Some(Self::Overwrite),
+            "reject" => Some(Self::Reject),
+            "number" => Some(Self::Number),
+            "hash" => Some(Self::Hash),
+            "version" => Some(Self::Version),
+            _ => None,
+        }
+    }
+
+    pub fn from_env() -> Self {
+        if let Some(policy) = env::var("CONFLICT_POLICY").ok().and_then(|v| Self::parse(&v)) {
+            return policy;
+        }
+        // VERSIONING=true reste un raccourci pour CONFLICT_POLICY=version
+        let versioning = env::var("VERSIONING")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        if versioning {
+            Self::Version
+        } else {
+            Self::Timestamp
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Timestamp => "timestamp",
+            Self::Overwrite => "overwrite",
+            Self::Reject => "reject",
+            Self::Number => "number",
+            Self::Hash => "hash",
+            Self::Version => "version",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Timestamp => "Renommer (horodatage)",
+            Self::Overwrite => "Remplacer",
+            Self::Reject => "Refuser",
+            Self::Number => "Numéroter",
+            Self::Hash => "Garder les deux (hash)",
+            Self::Version => "Nouvelle version",
+        }
+    }
+
+    pub const ALL: [ConflictPolicy; 6] = [
+        Self::Timestamp,
+        Self::Number,
+        Self::Hash,
+        Self::Version,
+        Self::Overwrite,
+        Self::Reject,
+    ];
+}
+
+pub fn conflict_error(filename: &str) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::AlreadyExists,
+        format!("Un fichier nommé {} existe déjà", filename),
+    )
+}
+
+// Choisit le nom final de `staged` parmi les fichiers partagés
+pub fn resolve(upload_dir: &str, staged: &Path, filename: &str, policy: ConflictPolicy) -> std::io::Result<Target> {
+    // Un nom à la fois, par l'index SQLite, sans lister le stockage
+    let taken = |name: &str| names::find(upload_dir, name);
+    let Some(existing) = taken(filename) else {
+        return Ok(Target::New(filename.to_string()));
+    };
+
+    let (stem, extension) = split_name(filename);
+    match policy {
+        ConflictPolicy::Timestamp => Ok(Target::New(timestamped_name(taken, &stem, &extension))),
+        ConflictPolicy::Overwrite | ConflictPolicy::Version => Ok(Target::Replace(existing)),
+        ConflictPolicy::Reject => Err(conflict_error(filename)),
+        ConflictPolicy::Number => Ok(Target::New(
+            (1..)
+                .map(|n| format!("{} ({}){}", stem, n, extension))
+                .find(|name| taken(name).is_none())
+                .unwrap(),
+        )),
+        ConflictPolicy::Hash => {
+            let hash = meta::file_sha256(staged)?;
+            let existing_hash = match meta::load(upload_dir, &existing).sha256 {
+                Some(hash) => hash,
+                None => storage::sha256(storage::open(upload_dir).as_ref(), &existing)?,
+            };
+            if existing_hash == hash {
+                return Ok(Target::Duplicate {
+                    id: existing,
+                    name: filename.to_string(),
+                });
+            }
+            let hashed = format!("{}-{}{}", stem, &hash[..8], extension);
+            match taken(&hashed) {
+                Some(id) => Ok(Target::Duplicate {
+                    id,
+                    name: hashed,
+                }),
+                None => Ok(Target::New(hashed)),
+            }
+        }
+    }
+}
+
+fn split_name(filename: &str) -> (String, String) {
+    let path = Path::new(filename);
+    let stem = path
+        .file_stem()
+        .map_or(filename.to_string(), |s| s.to_string_lossy().to_string());
+    let extension = path
+        .extension()
+        .map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
+    (stem, extension)
+}
+
+// "nom_<timestamp>.ext", avec un compteur si ce nom est lui aussi déjà pris
+// (plusieurs uploads du même fichier dans la même seconde)
+fn timestamped_name(taken: impl Fn(&str) -> Option<String>, stem: &str, extension: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let candidate = format!("{}_{}{}", stem, timestamp, extension);
+    if taken(&candidate).is_none() {
+        return candidate;
+    }
+    (2..)
+        .map(|n| format!("{}_{}_{}{}", stem, timestamp, n, extension))
+        .find(|name| taken(name).is_none())
+        .unwrap()
+}
@@ -0,0 +1,720 @@
+// Chiffrement au repos : le contenu des fichiers est chiffré par blocs de 64 Ko
+// (XChaCha20-Poly1305), ce qui permet de déchiffrer n'importe quelle plage sans
+// lire tout le fichier. La clé vient d'une phrase de passe (Argon2id) ou d'un fichier.
+//
+// Format : "SSENC\x01\0\0" + préfixe de nonce aléatoire (16 octets), puis les blocs
+// chiffrés (contenu + tag de 16 octets). Le nonce d'un bloc est le préfixe suivi de
+// son numéro, le bit de poids fort marquant le dernier bloc (fichier tronqué = erreur).
+use std::{
+    env, fs,
+    io::{self, BufRead, IsTerminal, Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
+};
+
+use argon2::Argon2;
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, KeyInit, OsRng},
+    XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::storage::{ObjectInfo, Storage, StorageConfig};
+
+const MAGIC: &[u8; 8] = b"SSENC\x01\0\0";
+const PREFIX_LEN: usize = 16;
+pub const HEADER_LEN: u64 = (MAGIC.len() + PREFIX_LEN) as u64;
+const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const SEALED_LEN: u64 = (CHUNK_LEN + TAG_LEN) as u64;
+const LAST_CHUNK: u64 = 1 << 63;
+// Paramètres de dérivation, stockés avec les fichiers : tous les serveurs
+// partageant un stockage doivent dériver la même clé
+const PARAMS_KEY: &str = ".encryption.json";
+
+static KEY: OnceLock<[u8; 32]> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+enum KeySource {
+    Disabled,
+    Passphrase(String),
+    // Demandée dans le terminal au démarrage
+    Prompt,
+    KeyFile(PathBuf),
+}
+
+#[derive(Debug, Clone)]
+pub struct EncryptionConfig {
+    source: KeySource,
+}
+
+impl EncryptionConfig {
+    pub fn from_env() -> Self {
+        let source = if let Ok(path) = env::var("ENCRYPTION_KEY_FILE") {
+            KeySource::KeyFile(PathBuf::from(path))
+        } else if let Ok(passphrase) = env::var("ENCRYPTION_PASSPHRASE") {
+            KeySource::Passphrase(passphrase)
+        } else if env::var("ENCRYPTION").is_ok_and(|v| v.eq_ignore_ascii_case("prompt")) {
+            KeySource::Prompt
+        } else {
+            KeySource::Disabled
+        };
+        Self { source }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self.source {
+            KeySource::Disabled => "désactivé",
+            KeySource::Passphrase(_) | KeySource::Prompt => "activé (phrase de passe)",
+            KeySource::KeyFile(_) => "activé (fichier de clé)",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyParams {
+    // "argon2id" ou "keyfile"
+    kdf: String,
+    #[serde(default)]
+    salt: String,
+    // Empreinte de la clé : une mauvaise phrase de passe est refusée au démarrage
+    check: String,
+}
+
+fn key_check(key: &[u8; 32]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(b"sharing_server key check");
+    hasher.update(key);
+    hex::encode(hasher.finalize())
+}
+
+fn read_key_file(path: &Path) -> io::Result<[u8; 32]> {
+    let content = fs::read(path)?;
+    // 32 octets bruts ou 64 caractères hexadécimaux (ex: `openssl rand -hex 32`)
+    let raw = match std::str::from_utf8(&content).map(str::trim) {
+        Ok(text) if text.len() == 64 => hex::decode(text).map_err(io::Error::other)?,
+        _ => content,
+    };
+    raw.try_into()
+        .map_err(|_| io::Error::other("le fichier de clé doit contenir 32 octets (ou 64 caractères hexadécimaux)"))
+}
+
+fn prompt_passphrase() -> io::Result<String> {
+    print!("🔐 Phrase de passe de chiffrement: ");
+    io::stdout().flush()?;
+    // Ne pas afficher la saisie
+    let hide = cfg!(unix) && io::stdin().is_terminal();
+    if hide {
+        let _ = Command::new("stty").arg("-echo").status();
+    }
+    let mut line = String::new();
+    let read = io::stdin().lock().read_line(&mut line);
+    if hide {
+        let _ = Command::new("stty").arg("echo").status();
+        println!();
+    }
+    read?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn derive(passphrase: &str, salt: &[u8]) -> io::Result<[u8; 32]> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    Ok(key)
+}
+
+// Au démarrage : dérive la clé et la vérifie avec les paramètres enregistrés
+// (créés au premier lancement). Renvoie false si le chiffrement est désactivé.
+pub fn init(upload_dir: &str, config: &EncryptionConfig, storage_config: &StorageConfig) -> io::Result<bool> {
+    let passphrase = match &config.source {
+        KeySource::Disabled => {
+            // Des fichiers déjà chiffrés seraient servis tels quels : on refuse de démarrer
+            if storage_config.open(upload_dir).stat(PARAMS_KEY)?.is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "fichiers chiffrés présents mais aucune clé configurée",
+                ));
+            }
+            return Ok(false);
+        }
+        KeySource::Passphrase(passphrase) => Some(passphrase.clone()),
+        KeySource::Prompt => Some(prompt_passphrase()?),
+        KeySource::KeyFile(_) => None,
+    };
+    if passphrase.as_deref().is_some_and(str::is_empty) {
+        return Err(io::Error::other("phrase de passe vide"));
+    }
+
+    let storage = storage_config.open(upload_dir);
+    let saved: Option<KeyParams> = match storage.stat(PARAMS_KEY)? {
+        Some(_) => {
+            let mut content = String::new();
+            storage.get(PARAMS_KEY, None)?.read_to_string(&mut content)?;
+            Some(serde_json::from_str(&content).map_err(io::Error::other)?)
+        }
+        None => None,
+    };
+
+    let (key, kdf, salt) = match (&config.source, passphrase) {
+        (KeySource::KeyFile(path), _) => (read_key_file(path)?, "keyfile", Vec::new()),
+        (_, Some(passphrase)) => {
+            let salt = match saved.as_ref().filter(|p| p.kdf == "argon2id") {
+                Some(params) => hex::decode(&params.salt).map_err(io::Error::other)?,
+                None => {
+                    let mut salt = vec![0u8; 16];
+                    OsRng.fill_bytes(&mut salt);
+                    salt
+                }
+            };
+            (derive(&passphrase, &salt)?, "argon2id", salt)
+        }
+        _ => unreachable!(),
+    };
+
+    match saved {
+        Some(params) if params.kdf != kdf || params.check != key_check(&key) => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "clé de chiffrement incorrecte pour les fichiers existants",
+            ));
+        }
+        Some(_) => {}
+        None => {
+            let params = KeyParams {
+                kdf: kdf.to_string(),
+                salt: hex::encode(&salt),
+                check: key_check(&key),
+            };
+            let content = serde_json::to_vec_pretty(&params).map_err(io::Error::other)?;
+            storage.put(PARAMS_KEY, &mut content.as_slice(), content.len() as u64)?;
+        }
+    }
+    let _ = KEY.set(key);
+    Ok(true)
+}
+
+pub fn enabled() -> bool {
+    KEY.get().is_some()
+}
+
+fn cipher() -> Option<XChaCha20Poly1305> {
+    KEY.get().map(|key| XChaCha20Poly1305::new(key.into()))
+}
+
+fn nonce(prefix: &[u8; PREFIX_LEN], index: u64, last: bool) -> XNonce {
+    let mut nonce = [0u8; 24];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    let counter = if last { index | LAST_CHUNK } else { index };
+    nonce[PREFIX_LEN..].copy_from_slice(&counter.to_be_bytes());
+    nonce.into()
+}
+
+fn seal(cipher: &XChaCha20Poly1305, prefix: &[u8; PREFIX_LEN], index: u64, last: bool, chunk: &[u8]) -> io::Result<Vec<u8>> {
+    cipher
+        .encrypt(&nonce(prefix, index, last), chunk)
+        .map_err(|_| io::Error::other("erreur de chiffrement"))
+}
+
+fn header() -> ([u8; PREFIX_LEN], Vec<u8>) {
+    let mut prefix = [0u8; PREFIX_LEN];
+    OsRng.fill_bytes(&mut prefix);
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&prefix);
+    (prefix, header)
+}
+
+fn chunk_count(plain_size: u64) -> u64 {
+    plain_size.div_ceil(CHUNK_LEN as u64).max(1)
+}
+
+pub fn sealed_size(plain_size: u64) -> u64 {
+    HEADER_LEN + plain_size + chunk_count(plain_size) * TAG_LEN as u64
+}
+
+// Taille du contenu d'un fichier chiffré de `sealed_size` octets
+fn plain_size(sealed_size: u64) -> u64 {
+    let body = sealed_size.saturating_sub(HEADER_LEN);
+    body.saturating_sub(body.div_ceil(SEALED_LEN) * TAG_LEN as u64)
+}
+
+fn parse_header(header: &[u8]) -> Option<[u8; PREFIX_LEN]> {
+    (header.len() == HEADER_LEN as usize && header.starts_with(MAGIC))
+        .then(|| header[MAGIC.len()..].try_into().unwrap())
+}
+
+// Écriture d'un fichier pendant l'upload : chiffré si le chiffrement est actif
+pub enum StagedWriter {
+    Plain(fs::File),
+    Sealed {
+        file: fs::File,
+        cipher: XChaCha20Poly1305,
+        prefix: [u8; PREFIX_LEN],
+        index: u64,
+        buf: Vec<u8>,
+    },
+}
+
+impl StagedWriter {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut file = fs::File::create(path)?;
+        let Some(cipher) = cipher() else {
+            return Ok(Self::Plain(file));
+        };
+        let (prefix, header) = header();
+        file.write_all(&header)?;
+        Ok(Self::Sealed {
+            file,
+            cipher,
+            prefix,
+            index: 0,
+            buf: Vec::with_capacity(CHUNK_LEN),
+        })
+    }
+
+    pub fn write_all(&mut self, mut data: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.write_all(data),
+            Self::Sealed { file, cipher, prefix, index, buf } => {
+                while !data.is_empty() {
+                    // Un bloc plein n'est scellé qu'une fois la suite reçue :
+                    // le dernier bloc doit être marqué comme tel
+                    if buf.len() == CHUNK_LEN {
+                        file.write_all(&seal(cipher, prefix, *index, false, buf)?)?;
+                        *index += 1;
+                        buf.clear();
+                    }
+                    let n = data.len().min(CHUNK_LEN - buf.len());
+                    buf.extend_from_slice(&data[..n]);
+                    data = &data[n..];
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self {
+            Self::Plain(file) => file.sync_all(),
+            Self::Sealed { mut file, cipher, prefix, index, buf } => {
+                file.write_all(&seal(&cipher, &prefix, index, true, &buf)?)?;
+                file.sync_all()
+            }
+        }
+    }
+}
+
+// Chiffre un flux de taille connue au fil de la lecture
+struct SealReader<R> {
+    inner: R,
+    cipher: XChaCha20Poly1305,
+    prefix: [u8; PREFIX_LEN],
+    index: u64,
+    chunks: u64,
+    remaining: u64,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Read for SealReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.out.len() {
+            if self.index == self.chunks {
+                return Ok(0);
+            }
+            let len = self.remaining.min(CHUNK_LEN as u64) as usize;
+            let mut chunk = vec![0u8; len];
+            self.inner.read_exact(&mut chunk)?;
+            self.remaining -= len as u64;
+            let last = self.index + 1 == self.chunks;
+            self.out = seal(&self.cipher, &self.prefix, self.index, last, &chunk)?;
+            self.pos = 0;
+            self.index += 1;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// Déchiffre les blocs à partir du bloc `index` (le flux doit commencer au début de ce bloc)
+struct OpenReader<R> {
+    inner: R,
+    cipher: XChaCha20Poly1305,
+    prefix: [u8; PREFIX_LEN],
+    index: u64,
+    last: u64,
+    // Octets à ignorer au début du premier bloc (début de plage)
+    skip: usize,
+    out: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> Read for OpenReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.out.len() {
+            if self.index > self.last {
+                return Ok(0);
+            }
+            let mut sealed = Vec::with_capacity(SEALED_LEN as usize);
+            (&mut self.inner).take(SEALED_LEN).read_to_end(&mut sealed)?;
+            let is_last = self.index == self.last;
+            self.out = self
+                .cipher
+                .decrypt(&nonce(&self.prefix, self.index, is_last), sealed.as_slice())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "contenu chiffré altéré ou clé incorrecte"))?;
+            self.pos = self.skip.min(self.out.len());
+            self.skip = 0;
+            self.index += 1;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+// Lecteur du contenu en clair d'un fichier local (staging, analyse), chiffré ou non
+pub fn open_file(path: &Path) -> io::Result<Box<dyn Read + Send>> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut head = Vec::with_capacity(HEADER_LEN as usize);
+    (&mut file).take(HEADER_LEN).read_to_end(&mut head)?;
+    match (parse_header(&head), cipher()) {
+        (Some(prefix), Some(cipher)) => Ok(Box::new(OpenReader {
+            inner: file,
+            cipher,
+            prefix,
+            index: 0,
+            last: chunk_count(plain_size(size)) - 1,
+            skip: 0,
+            out: Vec::new(),
+            pos: 0,
+        })),
+        (Some(_), None) => Err(encrypted_without_key()),
+        (None, _) => Ok(Box::new(io::Cursor::new(head).chain(file))),
+    }
+}
+
+// Fichier local au format chiffré
+pub fn is_sealed(path: &Path) -> io::Result<bool> {
+    let mut head = Vec::with_capacity(HEADER_LEN as usize);
+    fs::File::open(path)?.take(HEADER_LEN).read_to_end(&mut head)?;
+    Ok(parse_header(&head).is_some())
+}
+
+// Taille du contenu en clair d'un fichier local
+pub fn file_size(path: &Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+    let mut head = Vec::with_capacity(HEADER_LEN as usize);
+    (&mut file).take(HEADER_LEN).read_to_end(&mut head)?;
+    Ok(if parse_header(&head).is_some() { plain_size(size) } else { size })
+}
+
+fn encrypted_without_key() -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, "fichier chiffré, clé de chiffrement non configurée")
+}
+
+// Stockage chiffré : put/get/stat/list travaillent sur le contenu en clair. Les
+// déplacements (put_file, get_file, rename) gardent les octets tels quels, le
+// staging utilisant le même format. Les fichiers stockés avant l'activation du
+// chiffrement restent lisibles en clair.
+pub struct EncryptedStorage {
+    inner: Box<dyn Storage>,
+    cipher: XChaCha20Poly1305,
+}
+
+impl EncryptedStorage {
+    pub fn wrap(inner: Box<dyn Storage>) -> Box<dyn Storage> {
+        match cipher() {
+            Some(cipher) => Box::new(Self { inner, cipher }),
+            None => inner,
+        }
+    }
+
+    fn prefix(&self, key: &str, size: u64) -> io::Result<Option<[u8; PREFIX_LEN]>> {
+        if size < HEADER_LEN {
+            return Ok(None);
+        }
+        let mut head = Vec::with_capacity(HEADER_LEN as usize);
+        self.inner.get(key, Some(0..HEADER_LEN))?.read_to_end(&mut head)?;
+        Ok(parse_header(&head))
+    }
+
+    fn plain_info(&self, mut info: ObjectInfo) -> io::Result<ObjectInfo> {
+        if self.prefix(&info.key, info.size)?.is_some() {
+            info.size = plain_size(info.size);
+        }
+        Ok(info)
+    }
+}
+
+impl Storage for EncryptedStorage {
+    fn put(&self, key: &str, data: &mut dyn Read, size: u64) -> io::Result<()> {
+        let (prefix, header) = header();
+        let mut sealed = io::Cursor::new(header).chain(SealReader {
+            inner: data,
+            cipher: self.cipher.clone(),
+            prefix,
+            index: 0,
+            chunks: chunk_count(size),
+            remaining: size,
+            out: Vec::new(),
+            pos: 0,
+        });
+        self.inner.put(key, &mut sealed, sealed_size(size))
+    }
+
+    fn get(&self, key: &str, range: Option<Range<u64>>) -> io::Result<Box<dyn Read + Send>> {
+        let size = self
+            .inner
+            .stat(key)?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, key.to_string()))?
+            .size;
+        let Some(prefix) = self.prefix(key, size)? else {
+            return self.inner.get(key, range);
+        };
+        let plain = plain_size(size);
+        let range = range.unwrap_or(0..plain);
+        let range = range.start.min(plain)..range.end.min(plain);
+        if range.is_empty() {
+            return Ok(Box::new(io::empty()));
+        }
+        let first = range.start / CHUNK_LEN as u64;
+        let last = (range.end - 1) / CHUNK_LEN as u64;
+        let sealed_range = HEADER_LEN + first * SEALED_LEN..(HEADER_LEN + (last + 1) * SEALED_LEN).min(size);
+        let reader = OpenReader {
+            inner: self.inner.get(key, Some(sealed_range))?,
+            cipher: self.cipher.clone(),
+            prefix,
+            index: first,
+            last: chunk_count(plain) - 1,
+            skip: (range.start - first * CHUNK_LEN as u64) as usize,
+            out: Vec::new(),
+            pos: 0,
+        };
+        Ok(Box::new(reader.take(range.end - range.start)))
+    }
+
+    fn stat(&self, key: &str) -> io::Result<Option<ObjectInfo>> {
+        self.inner.stat(key)?.map(|info| self.plain_info(info)).transpose()
+    }
+
+    fn list(&self, prefix: &str) -> io::Result<Vec<ObjectInfo>> {
+        self.inner
+            .list(prefix)?
+            .into_iter()
+            .map(|info| self.plain_info(info))
+            .collect()
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.inner.delete(key)
+    }
+
+    fn put_file(&self, key: &str, path: &Path) -> io::Result<()> {
+        self.inner.put_file(key, path)
+    }
+
+    fn get_file(&self, key: &str, path: &Path) -> io::Result<()> {
+        self.inner.get_file(key, path)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        self.inner.rename(from, to)
+    }
+
+    fn external_size(&self) -> io::Result<u64> {
+        self.inner.external_size()
+    }
+}
+
+// Au démarrage : chiffre les fichiers partagés stockés en clair avant l'activation
+// du chiffrement. Renvoie le nombre de fichiers chiffrés. Avec la déduplication,
+// le blob partagé est remplacé par sa version chiffrée et les copies y sont reliées.
+pub fn encrypt_existing(upload_dir: &str, storage_config: &StorageConfig, dedup: bool) -> io::Result<usize> {
+    let raw = storage_config.open(upload_dir);
+    let encrypted = EncryptedStorage::wrap(storage_config.open(upload_dir));
+    let mut count = 0;
+    for object in raw.list("")? {
+        if !crate::names::valid_id(&object.key) {
+            continue;
+        }
+        let size = object.size;
+        let head_len = HEADER_LEN.min(size);
+        let mut head = Vec::new();
+        raw.get(&object.key, Some(0..head_len))?.read_to_end(&mut head)?;
+        if parse_header(&head).is_some() {
+            continue;
+        }
+        encrypted.put(&object.key, &mut raw.get(&object.key, None)?, size)?;
+        if dedup {
+            crate::dedup::relink(upload_dir, &object.key)?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::LocalStorage;
+
+    const CHUNK: u64 = CHUNK_LEN as u64;
+    const SIZES: [u64; 9] = [0, 1, CHUNK - 1, CHUNK, CHUNK + 1, 2 * CHUNK - 1, 2 * CHUNK, 2 * CHUNK + 1, 3 * CHUNK + 100];
+
+    // Clé fixe partagée par les tests du module
+    fn test_cipher() -> XChaCha20Poly1305 {
+        KEY.get_or_init(|| [7u8; 32]);
+        cipher().unwrap()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("sharing-crypto-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn content(size: u64) -> Vec<u8> {
+        (0..size).map(|i| (i * 31 % 251) as u8).collect()
+    }
+
+    fn storage(dir: &Path) -> EncryptedStorage {
+        EncryptedStorage {
+            inner: Box::new(LocalStorage::new(dir.to_str().unwrap())),
+            cipher: test_cipher(),
+        }
+    }
+
+    fn read(storage: &dyn Storage, key: &str, range: Option<Range<u64>>) -> io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        storage.get(key, range)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn sizes_around_chunk_boundaries() {
+        assert_eq!(chunk_count(0), 1);
+        assert_eq!(chunk_count(CHUNK), 1);
+        assert_eq!(chunk_count(CHUNK + 1), 2);
+        assert_eq!(sealed_size(0), HEADER_LEN + TAG_LEN as u64);
+        assert_eq!(sealed_size(CHUNK), HEADER_LEN + SEALED_LEN);
+        assert_eq!(sealed_size(CHUNK + 1), HEADER_LEN + SEALED_LEN + 1 + TAG_LEN as u64);
+        for size in SIZES {
+            assert_eq!(plain_size(sealed_size(size)), size, "taille {}", size);
+        }
+    }
+
+    #[test]
+    fn last_chunk_flag_in_nonce() {
+        let prefix = [3u8; PREFIX_LEN];
+        let nonce_of = |index, last| nonce(&prefix, index, last).to_vec();
+        assert_eq!(&nonce_of(5, false)[..PREFIX_LEN], &prefix);
+        assert_eq!(&nonce_of(5, false)[PREFIX_LEN..], &5u64.to_be_bytes());
+        assert_eq!(&nonce_of(5, true)[PREFIX_LEN..], &(5u64 | 1 << 63).to_be_bytes());
+        assert_ne!(nonce_of(0, true), nonce_of(0, false));
+    }
+
+    #[test]
+    fn round_trip_around_chunk_boundaries() {
+        let dir = temp_dir("roundtrip");
+        let storage = storage(&dir);
+        for size in SIZES {
+            let key = format!("f{}", size);
+            let data = content(size);
+            storage.put(&key, &mut data.as_slice(), size).unwrap();
+            assert_eq!(fs::metadata(dir.join(&key)).unwrap().len(), sealed_size(size));
+            assert_eq!(storage.stat(&key).unwrap().unwrap().size, size);
+            assert_eq!(read(&storage, &key, None).unwrap(), data, "taille {}", size);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ranges_around_chunk_boundaries() {
+        let dir = temp_dir("ranges");
+        let storage = storage(&dir);
+        let size = 3 * CHUNK + 100;
+        let data = content(size);
+        storage.put("f", &mut data.as_slice(), size).unwrap();
+        let ranges = [
+            0..1,
+            0..CHUNK,
+            CHUNK - 1..CHUNK + 1,
+            CHUNK..2 * CHUNK,
+            CHUNK + 10..CHUNK + 20,
+            2 * CHUNK - 5..3 * CHUNK + 100,
+            3 * CHUNK..3 * CHUNK + 100,
+            size - 1..size,
+        ];
+        for range in ranges {
+            let expected = &data[range.start as usize..range.end as usize];
+            assert_eq!(read(&storage, "f", Some(range.clone())).unwrap(), expected, "plage {:?}", range);
+        }
+        // Plages au-delà de la fin : tronquées, ou vides
+        assert_eq!(read(&storage, "f", Some(size - 10..size + 50)).unwrap(), &data[size as usize - 10..]);
+        assert!(read(&storage, "f", Some(size..size + 10)).unwrap().is_empty());
+        assert!(read(&storage, "f", Some(7..7)).unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn truncated_or_altered_content_is_refused() {
+        let dir = temp_dir("tamper");
+        let storage = storage(&dir);
+        let data = content(2 * CHUNK);
+        storage.put("f", &mut data.as_slice(), 2 * CHUNK).unwrap();
+        let raw = fs::read(dir.join("f")).unwrap();
+
+        // Dernier bloc retiré : le premier bloc n'est pas marqué comme dernier
+        fs::write(dir.join("f"), &raw[..(HEADER_LEN + SEALED_LEN) as usize]).unwrap();
+        let error = read(&storage, "f", None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut altered = raw.clone();
+        altered[HEADER_LEN as usize + 100] ^= 1;
+        fs::write(dir.join("f"), &altered).unwrap();
+        assert!(read(&storage, "f", Some(0..10)).is_err());
+        // Le bloc intact reste lisible seul
+        assert_eq!(read(&storage, "f", Some(CHUNK..CHUNK + 10)).unwrap(), &data[CHUNK as usize..CHUNK as usize + 10]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn staged_writer_matches_storage_format() {
+        test_cipher();
+        let dir = temp_dir("staged");
+        let storage = storage(&dir);
+        for size in SIZES {
+            let data = content(size);
+            let path = dir.join(format!("staged{}", size));
+            let mut writer = StagedWriter::create(&path).unwrap();
+            // Écritures de tailles irrégulières, à cheval sur les blocs
+            for piece in data.chunks(CHUNK_LEN / 3 + 7) {
+                writer.write_all(piece).unwrap();
+            }
+            writer.finish().unwrap();
+            assert!(is_sealed(&path).unwrap());
+            assert_eq!(file_size(&path).unwrap(), size);
+            let mut plain = Vec::new();
+            open_file(&path).unwrap().read_to_end(&mut plain).unwrap();
+            assert_eq!(plain, data, "taille {}", size);
+
+            let key = format!("f{}", size);
+            storage.put_file(&key, &path).unwrap();
+            assert_eq!(read(&storage, &key, None).unwrap(), data);
+            if size > 2 {
+                assert_eq!(read(&storage, &key, Some(1..size - 1)).unwrap(), &data[1..size as usize - 1]);
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,172 @@
+// Stockage dédupliqué : chaque contenu est stocké une seule fois dans .blobs/<sha256>,
+// les fichiers partagés (et leurs versions, la corbeille) en sont des liens physiques.
+// Le nombre de liens sert de compteur de références.
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    crypto, meta, names,
+    storage::{self, StorageConfig},
+    store, STAGING_DIR,
+};
+
+const BLOBS_DIR: &str = ".blobs";
+
+#[derive(Debug, Clone)]
+pub struct DedupConfig {
+    pub enabled: bool,
+}
+
+impl DedupConfig {
+    pub fn from_env() -> Self {
+        let requested = env::var("DEDUP")
+            .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+            .unwrap_or(false);
+        // Sans compteur de liens, les contenus inutilisés ne pourraient jamais être libérés
+        let enabled = requested && cfg!(unix) && StorageConfig::from_env().is_local();
+        if requested && !enabled {
+            warn!("⚠️ DEDUP n'est disponible que sur les systèmes unix avec le stockage local, déduplication désactivée");
+        }
+        Self { enabled }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        if self.enabled {
+            "activée"
+        } else {
+            "désactivée"
+        }
+    }
+}
+
+fn blobs_dir(upload_dir: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(BLOBS_DIR)
+}
+
+fn blob_path(upload_dir: &str, sha256: &str) -> Option<PathBuf> {
+    // Le nom du blob vient des métadonnées : n'accepter qu'une empreinte hexadécimale
+    (sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| blobs_dir(upload_dir).join(sha256.to_lowercase()))
+}
+
+#[cfg(unix)]
+fn link_count(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| m.nlink())
+}
+
+#[cfg(not(unix))]
+fn link_count(_path: &Path) -> Option<u64> {
+    None
+}
+
+// Nom (d'affichage) d'un fichier partagé ayant déjà ce contenu
+pub fn existing_copy(upload_dir: &str, sha256: &str, except_id: Option<&str>) -> Option<String> {
+    let storage = storage::open(upload_dir);
+    store::find_by_sha256(upload_dir, sha256)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|(id, _)| Some(id.as_str()) != except_id)
+        .find(|(id, _)| storage.stat(id).is_ok_and(|info| info.is_some()))
+        .map(|(id, file_meta)| file_meta.name.unwrap_or(id))
+}
+
+// Place un fichier reçu à `destination` : le contenu rejoint le magasin de blobs
+// (ou y est déjà) et `destination` devient un lien vers ce blob
+pub fn place(upload_dir: &str, staged: &Path, destination: &Path, sha256: &str) -> std::io::Result<()> {
+    let Some(blob) = blob_path(upload_dir, sha256) else {
+        return fs::rename(staged, destination);
+    };
+    fs::create_dir_all(blobs_dir(upload_dir))?;
+    if blob.exists() {
+        fs::remove_file(staged)?;
+    } else {
+        fs::rename(staged, &blob)?;
+    }
+    // Lien créé à côté puis renommé : remplace atomiquement un fichier existant
+    let link = PathBuf::from(upload_dir)
+        .join(STAGING_DIR)
+        .join(format!("{}.link", names::new_id(upload_dir)));
+    fs::create_dir_all(link.parent().unwrap())?;
+    fs::hard_link(&blob, &link)?;
+    fs::rename(&link, destination)
+}
+
+// Appelé quand un fichier qui référençait ce contenu a été supprimé :
+// le blob n'est effacé que s'il n'est plus lié nulle part
+pub fn release(upload_dir: &str, sha256: Option<&str>) {
+    let Some(blob) = sha256.and_then(|sha| blob_path(upload_dir, sha)) else {
+        return;
+    };
+    if link_count(&blob).is_some_and(|links| links <= 1) {
+        match fs::remove_file(&blob) {
+            Ok(_) => info!(blob = %blob.file_name().unwrap().to_string_lossy(), "🧬 Contenu dédupliqué libéré"),
+            Err(e) => error!(blob = %blob.display(), error = %e, "❌ Erreur suppression contenu"),
+        }
+    }
+}
+
+// Filet de sécurité (nettoyeur périodique) : blobs qui ne sont plus référencés
+pub fn sweep(upload_dir: &str) {
+    let Ok(entries) = fs::read_dir(blobs_dir(upload_dir)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let sha256 = entry.file_name().to_string_lossy().to_string();
+        release(upload_dir, Some(&sha256));
+    }
+}
+
+// Après le chiffrement d'un fichier qui était lié à un blob en clair (le fichier
+// chiffré est une copie indépendante) : le premier fichier chiffré devient le blob,
+// les suivants y sont reliés. L'ancien blob disparaît avec son dernier lien.
+pub fn relink(upload_dir: &str, id: &str) -> std::io::Result<()> {
+    let Some(blob) = meta::load(upload_dir, id).sha256.and_then(|sha| blob_path(upload_dir, &sha)) else {
+        return Ok(());
+    };
+    let path = PathBuf::from(upload_dir).join(id);
+    if blob.exists() && crypto::is_sealed(&blob)? {
+        return place(upload_dir, &path, &path, &blob.file_name().unwrap().to_string_lossy());
+    }
+    fs::create_dir_all(blobs_dir(upload_dir))?;
+    let link = PathBuf::from(upload_dir)
+        .join(STAGING_DIR)
+        .join(format!("{}.link", names::new_id(upload_dir)));
+    fs::create_dir_all(link.parent().unwrap())?;
+    fs::hard_link(&path, &link)?;
+    fs::rename(&link, &blob)
+}
+
+// Au démarrage : les fichiers stockés avant l'activation de la déduplication
+// rejoignent le magasin de blobs. Renvoie le nombre d'octets économisés.
+pub fn adopt_existing(upload_dir: &str) -> std::io::Result<u64> {
+    let mut saved = 0;
+    let indexed = store::all(upload_dir).map_err(std::io::Error::other)?;
+    for (id, file_meta) in indexed {
+        let path = PathBuf::from(upload_dir).join(&id);
+        let Some(sha256) = file_meta.sha256.as_deref() else {
+            continue;
+        };
+        if !path.is_file() || link_count(&path).is_some_and(|links| links > 1) {
+            continue;
+        }
+        let Some(blob) = blob_path(upload_dir, sha256) else {
+            continue;
+        };
+        // Le fichier a pu être modifié sur le disque depuis son indexation
+        if meta::file_sha256(&path)? != sha256 {
+            continue;
+        }
+        if blob.exists() {
+            saved += fs::metadata(&path)?.len();
+            place(upload_dir, &path, &path, sha256)?;
+        } else {
+            fs::create_dir_all(blobs_dir(upload_dir))?;
+            fs::hard_link(&path, &blob)?;
+        }
+    }
+    Ok(saved)
+}
@@ -0,0 +1,204 @@
+// Sondes pour l'orchestrateur de conteneurs (vivant, prêt) et description
+// du serveur : version, durée de fonctionnement, limites, fonctions actives
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{get, http::header, web, HttpResponse, Result};
+use serde::Serialize;
+
+use crate::{crypto, get_local_ips, shutdown, Config, STAGING_DIR};
+
+// Date de démarrage, fixée par start() au lancement du serveur
+static STARTED: LazyLock<SystemTime> = LazyLock::new(SystemTime::now);
+
+// Durée de validité des vérifications coûteuses (fichier témoin, listage du
+// stockage) : chaque onglet ouvert interroge /readyz toutes les 10 s
+const CHECKS_TTL: Duration = Duration::from_secs(5);
+static CACHED_CHECKS: Mutex<Option<(Instant, Vec<Check>)>> = Mutex::new(None);
+
+pub fn start() {
+    LazyLock::force(&STARTED);
+}
+
+fn uptime() -> u64 {
+    STARTED.elapsed().map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[derive(Debug, Clone)]
+pub struct HealthConfig {
+    // Espace libre minimal pour se déclarer prêt
+    min_free: u64,
+}
+
+impl HealthConfig {
+    pub fn from_env() -> Self {
+        Self {
+            min_free: env::var("READY_MIN_FREE")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .unwrap_or(100)
+                * 1024
+                * 1024,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        format!("/healthz, /readyz (≥ {} MB libres)", self.min_free / (1024 * 1024))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Check {
+    name: &'static str,
+    ok: bool,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, result: std::result::Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self { name, ok: true, detail },
+            Err(detail) => Self { name, ok: false, detail },
+        }
+    }
+}
+
+// Un serveur en cours d'arrêt ne doit plus recevoir de trafic
+fn check_running() -> std::result::Result<String, String> {
+    if shutdown::in_progress() {
+        Err("arrêt en cours".to_string())
+    } else {
+        Ok("en service".to_string())
+    }
+}
+
+// Le dossier d'upload accepte une écriture (fichier témoin dans le staging)
+fn check_writable(upload_dir: &str) -> std::result::Result<String, String> {
+    let staging = PathBuf::from(upload_dir).join(STAGING_DIR);
+    let probe = staging.join(format!(".readyz-{}", std::process::id()));
+    fs::create_dir_all(&staging)
+        .and_then(|_| fs::write(&probe, b"ok"))
+        .and_then(|_| fs::remove_file(&probe))
+        .map(|_| "accessible en écriture".to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn check_free_space(upload_dir: &str, min_free: u64) -> std::result::Result<String, String> {
+    let available = fs4::available_space(upload_dir).map_err(|e| e.to_string())?;
+    let detail = format!("{} octets libres (minimum {})", available, min_free);
+    if available >= min_free {
+        Ok(detail)
+    } else {
+        Err(detail)
+    }
+}
+
+fn check_storage(config: &Config) -> std::result::Result<String, String> {
+    config
+        .storage
+        .open(&config.upload_dir)
+        .list("")
+        .map(|_| config.storage.describe())
+        .map_err(|e| format!("{}: {}", config.storage.describe(), e))
+}
+
+// Dossier inscriptible et stockage joignable, résultat réutilisé pendant
+// CHECKS_TTL. Le verrou est gardé pendant les vérifications : des requêtes
+// simultanées attendent le même résultat au lieu de les refaire.
+fn cached_checks(config: &Config) -> Vec<Check> {
+    let mut cached = CACHED_CHECKS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((_, checks)) = cached.as_ref().filter(|(at, _)| at.elapsed() < CHECKS_TTL) {
+        return checks.clone();
+    }
+    let checks = vec![
+        Check::new("upload_dir", check_writable(&config.upload_dir)),
+        Check::new("storage", check_storage(config)),
+    ];
+    *cached = Some((Instant::now(), checks.clone()));
+    checks
+}
+
+// Processus vivant : répond tant que le serveur HTTP tourne
+#[get("/healthz")]
+async fn healthz() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(serde_json::json!({
+            "status": "ok",
+            "uptime": uptime(),
+        }))
+}
+
+// Prêt à recevoir des fichiers : dossier inscriptible, espace libre, stockage joignable
+#[get("/readyz")]
+async fn readyz() -> Result<HttpResponse> {
+    let checks = web::block(|| {
+        let config = Config::from_env();
+        let mut checks = vec![
+            Check::new("shutdown", check_running()),
+            Check::new("free_space", check_free_space(&config.upload_dir, config.health.min_free)),
+        ];
+        checks.extend(cached_checks(&config));
+        checks
+    })
+    .await?;
+    let ready = checks.iter().all(|check| check.ok);
+    let mut response = if ready {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    Ok(response
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(serde_json::json!({
+            "status": if ready { "ok" } else { "unavailable" },
+            "checks": checks,
+        })))
+}
+
+#[get("/api/v1/info")]
+async fn info() -> HttpResponse {
+    let config = Config::from_env();
+    let mut urls: Vec<String> = get_local_ips()
+        .iter()
+        .map(|ip| format!("http://{}:{}", ip, config.port))
+        .collect();
+    urls.push(format!("http://localhost:{}", config.port));
+    let started_at = STARTED.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+
+    HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(serde_json::json!({
+            "name": env!("CARGO_PKG_NAME"),
+            "version": env!("CARGO_PKG_VERSION"),
+            "started_at": started_at,
+            "uptime": uptime(),
+            "urls": urls,
+            "limits": {
+                "max_file_size": config.max_file_size,
+                "quota": config.quota.describe(),
+                "default_ttl": config.retention.default_ttl,
+                "max_versions": config.versioning.max_versions,
+                "rate_limits": config.rate_limit.describe(),
+                "bandwidth": config.throttle.describe(),
+                "shutdown_timeout": config.shutdown.timeout,
+            },
+            "features": {
+                "storage": config.storage.describe(),
+                "encryption": crypto::enabled(),
+                "deduplication": config.dedup.enabled,
+                "antivirus": config.scan.is_enabled(),
+                "secure_shares": config.secure.enabled,
+                "compression": config.compression.enabled,
+                "audit_log": config.audit.enabled,
+                "metrics": config.metrics.enabled,
+                "webhooks": config.webhook.enabled(),
+                "checksums": config.checksum.describe(),
+                "conflict_policy": config.conflict_policy.as_str(),
+            },
+        }))
+}
@@ -0,0 +1,288 @@
+// Sommes de contrôle : calculées pendant l'upload, vérifiables à la demande,
+// exposées dans les en-têtes de téléchargement et revérifiées en tâche de fond
+use std::{
+    env,
+    io::{self, Read},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    http::header::{self, HeaderValue},
+    post, web, HttpResponse, Result,
+};
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use sha2::{Digest, Sha256};
+use tracing::{error, info, warn};
+
+use crate::{
+    get_uploaded_files,
+    meta::{self, FileMeta},
+    names,
+    retention::{format_duration, parse_ttl},
+    storage::{self, Storage},
+    Config,
+};
+
+#[derive(Debug, Clone)]
+pub struct ChecksumConfig {
+    // Calculer aussi une empreinte BLAKE3 (plus rapide à revérifier)
+    pub blake3: bool,
+    // Intervalle entre deux vérifications complètes des fichiers (None = jamais)
+    scrub_interval: Option<Duration>,
+}
+
+impl ChecksumConfig {
+    pub fn from_env() -> Self {
+        Self {
+            blake3: env::var("CHECKSUM_BLAKE3")
+                .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes" | "on"))
+                .unwrap_or(false),
+            scrub_interval: env::var("SCRUB_INTERVAL")
+                .ok()
+                .and_then(|v| parse_ttl(&v))
+                .map(Duration::from_secs),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let algorithms = if self.blake3 { "SHA-256 + BLAKE3" } else { "SHA-256" };
+        match self.scrub_interval {
+            Some(interval) => format!("{}, vérification toutes les {}", algorithms, format_duration(interval.as_secs())),
+            None => algorithms.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksums {
+    pub sha256: String,
+    pub blake3: Option<String>,
+}
+
+// Empreintes calculées au fil de l'eau, sans relire le fichier
+pub struct Hasher {
+    sha256: Sha256,
+    blake3: Option<blake3::Hasher>,
+}
+
+impl Hasher {
+    pub fn new(blake3: bool) -> Self {
+        Self {
+            sha256: Sha256::new(),
+            blake3: blake3.then(blake3::Hasher::new),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.sha256.update(data);
+        if let Some(hasher) = &mut self.blake3 {
+            hasher.update(data);
+        }
+    }
+
+    pub fn finalize(self) -> Checksums {
+        Checksums {
+            sha256: hex::encode(self.sha256.finalize()),
+            blake3: self.blake3.map(|hasher| hasher.finalize().to_hex().to_string()),
+        }
+    }
+}
+
+// Somme attendue, fournie par le client
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expected {
+    Sha256(String),
+    Blake3(String),
+}
+
+impl Expected {
+    // Formats acceptés : "<hex>" (SHA-256), "sha256:<hex>", "blake3:<hex>",
+    // "sha-256=<base64>" (comme l'en-tête Digest)
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let is_hex = |v: &str| v.len() == 64 && v.chars().all(|c| c.is_ascii_hexdigit());
+        let (algorithm, digest) = value
+            .split_once([':', '='])
+            .map_or(("sha256", value), |(a, d)| (a.trim(), d.trim()));
+        match algorithm.to_lowercase().as_str() {
+            "sha256" | "sha-256" if is_hex(digest) => Some(Self::Sha256(digest.to_lowercase())),
+            "sha256" | "sha-256" => STANDARD
+                .decode(digest.trim_matches(':'))
+                .ok()
+                .filter(|bytes| bytes.len() == 32)
+                .map(|bytes| Self::Sha256(hex::encode(bytes))),
+            "blake3" if is_hex(digest) => Some(Self::Blake3(digest.to_lowercase())),
+            _ => None,
+        }
+    }
+
+    pub fn needs_blake3(&self) -> bool {
+        matches!(self, Self::Blake3(_))
+    }
+
+    pub fn matches(&self, sums: &Checksums) -> bool {
+        match self {
+            Self::Sha256(expected) => *expected == sums.sha256,
+            Self::Blake3(expected) => sums.blake3.as_ref() == Some(expected),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Sha256(digest) => format!("sha256:{}", digest),
+            Self::Blake3(digest) => format!("blake3:{}", digest),
+        }
+    }
+}
+
+// Empreintes du contenu d'un objet stocké (en clair si le stockage est chiffré)
+pub fn compute(storage: &dyn Storage, key: &str, blake3: bool) -> io::Result<Checksums> {
+    let mut reader = storage.get(key, None)?;
+    let mut hasher = Hasher::new(blake3);
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
+// Digest (RFC 3230) et Repr-Digest (RFC 9530) d'un téléchargement, l'ETag
+// est posé avec les autres validateurs (cache.rs)
+pub fn add_headers(response: &mut HttpResponse, file_meta: &FileMeta) {
+    if !response.status().is_success() {
+        return;
+    }
+    let Some(digest) = file_meta.sha256.as_deref().and_then(|sha| hex::decode(sha).ok()) else {
+        return;
+    };
+    let encoded = STANDARD.encode(&digest);
+    let headers = response.headers_mut();
+    for (name, value) in [
+        (header::HeaderName::from_static("digest"), format!("sha-256={}", encoded)),
+        (header::HeaderName::from_static("repr-digest"), format!("sha-256=:{}:", encoded)),
+    ] {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[derive(Debug)]
+pub struct Verification {
+    pub ok: bool,
+    pub expected: Option<String>,
+    pub actual: Checksums,
+}
+
+// Recalcule les empreintes d'un fichier partagé et enregistre le résultat.
+// Un fichier sans empreinte connue (déposé avant leur calcul) reçoit celle calculée.
+pub fn verify(upload_dir: &str, id: &str) -> io::Result<Verification> {
+    let storage = storage::open(upload_dir);
+    let mut file_meta = meta::load(upload_dir, id);
+    let actual = compute(storage.as_ref(), id, file_meta.blake3.is_some())?;
+    let expected = file_meta.sha256.clone();
+    let ok = expected.as_ref().is_none_or(|sha| *sha == actual.sha256)
+        && file_meta.blake3.as_ref().is_none_or(|b3| actual.blake3.as_ref() == Some(b3));
+
+    if expected.is_none() {
+        file_meta.sha256 = Some(actual.sha256.clone());
+    }
+    file_meta.verified_at = Some(now());
+    file_meta.corrupt = !ok;
+    meta::save(upload_dir, id, &file_meta)?;
+    Ok(Verification { ok, expected, actual })
+}
+
+// Tâche de fond : revérifie régulièrement tous les fichiers partagés
+pub fn spawn_scrubber(upload_dir: String, config: &ChecksumConfig) {
+    let Some(period) = config.scrub_interval else {
+        return;
+    };
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(period);
+        // Pas de vérification complète au démarrage
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let upload_dir = upload_dir.clone();
+            if let Err(e) = web::block(move || scrub(&upload_dir)).await {
+                error!(error = %e, "❌ Erreur vérification d'intégrité");
+            }
+        }
+    });
+}
+
+fn scrub(upload_dir: &str) {
+    let files = get_uploaded_files(upload_dir);
+    let mut corrupt = 0;
+    for file in &files {
+        match verify(upload_dir, &file.id) {
+            Ok(result) if result.ok => {}
+            Ok(result) => {
+                corrupt += 1;
+                error!(
+                    file = %file.name,
+                    expected = result.expected.as_deref().unwrap_or("?"),
+                    actual = %result.actual.sha256,
+                    "❌ Fichier corrompu"
+                );
+            }
+            Err(e) => warn!(file = %file.name, error = %e, "⚠️ Vérification impossible"),
+        }
+    }
+    info!(files = files.len(), corrupt, "🧾 Vérification d'intégrité terminée");
+}
+
+// Vérification à la demande d'un fichier partagé
+#[post("/files/{id}/verify")]
+async fn verify_file(path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    let not_found = || {
+        HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Fichier non trouvé"
+        }))
+    };
+    if !names::valid_id(&id) {
+        return Ok(not_found());
+    }
+
+    let upload_dir = config.upload_dir.clone();
+    let file_id = id.clone();
+    match web::block(move || verify(&upload_dir, &file_id)).await? {
+        Ok(result) => {
+            let name = names::display_name(&config.upload_dir, &id);
+            if result.ok {
+                info!(file = %name, "🧾 Intégrité vérifiée");
+            } else {
+                error!(file = %name, "❌ Fichier corrompu");
+            }
+            Ok(HttpResponse::Ok().json(serde_json::json!({
+                "id": id,
+                "name": name,
+                "ok": result.ok,
+                "sha256": result.actual.sha256,
+                "blake3": result.actual.blake3,
+                "expected_sha256": result.expected,
+            })))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(not_found()),
+        Err(e) => {
+            error!(error = %e, "❌ Erreur vérification d'intégrité");
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la vérification"
+            })))
+        }
+    }
+}
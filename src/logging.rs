@@ -0,0 +1,170 @@
+// Journalisation structurée (tracing) : un span par requête portant son
+// identifiant, renvoyé dans l'en-tête X-Request-Id et dans les corps d'erreur
+use std::{
+    env,
+    io::{self, IsTerminal},
+    time::Instant,
+};
+
+use actix_web::{
+    body::{self, BoxBody, EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{self, HeaderMap, HeaderName, HeaderValue},
+    middleware::Next,
+    Error,
+};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use tracing::Instrument;
+use tracing_subscriber::EnvFilter;
+
+use crate::access;
+
+pub const REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+// Identifiant reçu d'un proxy ou d'un client, repris s'il reste raisonnable
+const MAX_REQUEST_ID_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    // Une ligne lisible par événement
+    Pretty,
+    // Un objet JSON par ligne, pour un collecteur de logs
+    Json,
+}
+
+#[derive(Debug, Clone)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    // Filtre au format de RUST_LOG ("info", "warn,sharing_server=debug"...)
+    level: String,
+}
+
+impl LogConfig {
+    pub fn from_env() -> Self {
+        Self {
+            format: match env::var("LOG_FORMAT").unwrap_or_default().to_lowercase().as_str() {
+                "json" => LogFormat::Json,
+                _ => LogFormat::Pretty,
+            },
+            level: env::var("RUST_LOG")
+                .or_else(|_| env::var("LOG_LEVEL"))
+                .ok()
+                .filter(|v| !v.trim().is_empty())
+                .unwrap_or_else(|| "info".to_string()),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let format = match self.format {
+            LogFormat::Pretty => "texte",
+            LogFormat::Json => "JSON",
+        };
+        format!("{}, niveau {}", format, self.level)
+    }
+
+    // Installe le collecteur global ; les logs d'actix (crate log) y sont redirigés
+    pub fn init(&self) {
+        let filter = EnvFilter::try_new(&self.level).unwrap_or_else(|e| {
+            eprintln!("⚠️ Filtre de logs invalide \"{}\" ({}), niveau info utilisé", self.level, e);
+            EnvFilter::new("info")
+        });
+        let builder = tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_target(false)
+            .with_ansi(io::stdout().is_terminal());
+        match self.format {
+            LogFormat::Pretty => builder.init(),
+            LogFormat::Json => builder.json().with_current_span(true).with_span_list(false).init(),
+        }
+    }
+}
+
+fn new_request_id() -> String {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get(REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+        .map_or_else(new_request_id, str::to_string)
+}
+
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let id = request_id(req.headers());
+    let span = tracing::info_span!(
+        "requête",
+        request_id = %id,
+        method = %req.method(),
+        path = %req.path(),
+        client = %access::client_address(req.request()),
+    );
+    let started = Instant::now();
+    let mut response = next.call(req).instrument(span.clone()).await?;
+
+    let status = response.status();
+    let duration_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!(status = status.as_u16(), duration_ms, "❌ Requête en erreur");
+        } else {
+            tracing::info!(status = status.as_u16(), duration_ms, "↩️ Requête traitée");
+        }
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID, value);
+    }
+    if !(status.is_client_error() || status.is_server_error()) {
+        return Ok(response.map_into_left_body());
+    }
+    with_request_id(response, &id).await
+}
+
+// Ajoute l'identifiant de requête au corps d'une erreur : champ "request_id"
+// d'une erreur JSON, ou erreur texte convertie en {"error": ...}
+async fn with_request_id<B: MessageBody>(response: ServiceResponse<B>, id: &str) -> Result<ServiceResponse<EitherBody<B>>, Error> {
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let json = content_type.starts_with("application/json");
+    if !json && !content_type.starts_with("text/plain") {
+        return Ok(response.map_into_left_body());
+    }
+
+    let (req, res) = response.into_parts();
+    let (mut res, body) = res.into_parts();
+    let bytes = body::to_bytes(body).await.map_err(|e| {
+        let e: Box<dyn std::error::Error> = e.into();
+        actix_web::error::ErrorInternalServerError(e.to_string())
+    })?;
+    let value = if json {
+        serde_json::from_slice::<serde_json::Value>(&bytes).ok()
+    } else if bytes.is_empty() {
+        None
+    } else {
+        Some(serde_json::json!({ "error": String::from_utf8_lossy(&bytes) }))
+    };
+    let body = match value {
+        Some(serde_json::Value::Object(mut object)) => {
+            object.insert("request_id".to_string(), id.into());
+            res.headers_mut()
+                .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            BoxBody::new(serde_json::Value::Object(object).to_string())
+        }
+        _ => BoxBody::new(bytes),
+    };
+    Ok(ServiceResponse::new(req, res.set_body(body)).map_into_right_body())
+}
@@ -1,26 +1,94 @@
 use actix_multipart::Multipart;
 use actix_web::{
-    get, post, web, App, HttpResponse, HttpServer, Responder, Result,
-    middleware::{Logger, DefaultHeaders},
-    http::header::{ContentDisposition, DispositionType, DispositionParam},
+    get, post, web, App, HttpRequest, HttpResponse, HttpServer, Result,
+    middleware::{from_fn, Compress, Condition, DefaultHeaders},
+    http::header,
 };
 use futures_util::TryStreamExt as _;
 use if_addrs::get_if_addrs;
 use qrcode_generator::QrCodeEcc;
+use tracing::{error, info, warn};
 use std::{
+    collections::HashMap,
     env, fs,
-    io::Write,
     path::{Path, PathBuf},
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 use serde::{Deserialize, Serialize};
 
+mod access;
+mod audit;
+mod cache;
+mod compress;
+mod conflict;
+mod crypto;
+mod dedup;
+mod health;
+mod integrity;
+mod logging;
+mod meta;
+mod metrics;
+mod names;
+mod policy;
+mod quota;
+mod ratelimit;
+mod retention;
+mod scan;
+mod secure;
+mod shutdown;
+mod storage;
+mod store;
+mod throttle;
+mod trash;
+mod versions;
+mod webhook;
+
+use policy::{FileKind, UploadPolicy, SNIFF_LEN};
+use access::AccessConfig;
+use audit::AuditConfig;
+use compress::CompressionConfig;
+use conflict::{ConflictPolicy, Target};
+use crypto::{EncryptionConfig, StagedWriter};
+use dedup::DedupConfig;
+use health::HealthConfig;
+use integrity::{ChecksumConfig, Expected};
+use logging::LogConfig;
+use metrics::MetricsConfig;
+use meta::FileMeta;
+use quota::{QuotaConfig, StorageUsage};
+use ratelimit::RateLimitConfig;
+use retention::RetentionConfig;
+use trash::{TrashConfig, TrashEntry};
+use versions::{VersionEntry, VersioningConfig};
+use webhook::WebhookConfig;
+use scan::ScanConfig;
+use secure::SecureConfig;
+use shutdown::ShutdownConfig;
+use storage::{ObjectInfo, StorageConfig};
+use throttle::{Direction, ThrottleConfig, Transfer};
+
+// Dossier (caché) où les fichiers sont écrits pendant l'upload
+const STAGING_DIR: &str = ".staging";
+
 #[derive(Debug, Serialize, Deserialize)]
 struct FileInfo {
+    // Identifiant de stockage (nom du fichier sur le disque)
+    id: String,
+    // Nom d'origine affiché et proposé au téléchargement
     name: String,
     size: u64,
     uploaded_at: u64,
     file_type: String,
+    expires_at: Option<u64>,
+    uploader: Option<String>,
+    sha256: Option<String>,
+    blake3: Option<String>,
+    // Dernière vérification d'intégrité, et fichier trouvé corrompu
+    verified_at: Option<u64>,
+    corrupt: bool,
+    mime: Option<String>,
+    description: Option<String>,
+    tags: Vec<String>,
 }
 
 struct Config {
@@ -28,6 +96,27 @@ struct Config {
     refresh_interval: u32,
     upload_dir: String,
     max_file_size: usize,
+    upload_policy: UploadPolicy,
+    scan: ScanConfig,
+    quota: QuotaConfig,
+    retention: RetentionConfig,
+    trash: TrashConfig,
+    versioning: VersioningConfig,
+    conflict_policy: ConflictPolicy,
+    dedup: DedupConfig,
+    storage: StorageConfig,
+    secure: SecureConfig,
+    checksum: ChecksumConfig,
+    compression: CompressionConfig,
+    throttle: ThrottleConfig,
+    rate_limit: RateLimitConfig,
+    access: AccessConfig,
+    audit: AuditConfig,
+    logging: LogConfig,
+    metrics: MetricsConfig,
+    health: HealthConfig,
+    shutdown: ShutdownConfig,
+    webhook: WebhookConfig,
 }
 
 impl Config {
@@ -47,120 +136,612 @@ impl Config {
                 .unwrap_or_else(|_| "50".to_string())
                 .parse::<usize>()
                 .unwrap_or(50) * 1024 * 1024, // MB vers bytes
+            upload_policy: UploadPolicy::from_env(),
+            scan: ScanConfig::from_env(),
+            quota: QuotaConfig::from_env(),
+            retention: RetentionConfig::from_env(),
+            trash: TrashConfig::from_env(),
+            versioning: VersioningConfig::from_env(),
+            conflict_policy: ConflictPolicy::from_env(),
+            dedup: DedupConfig::from_env(),
+            storage: StorageConfig::from_env(),
+            secure: SecureConfig::from_env(),
+            checksum: ChecksumConfig::from_env(),
+            compression: CompressionConfig::from_env(),
+            throttle: ThrottleConfig::from_env(),
+            rate_limit: RateLimitConfig::from_env(),
+            access: AccessConfig::from_env(),
+            audit: AuditConfig::from_env(),
+            logging: LogConfig::from_env(),
+            metrics: MetricsConfig::from_env(),
+            health: HealthConfig::from_env(),
+            shutdown: ShutdownConfig::from_env(),
+            webhook: WebhookConfig::from_env(),
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+struct UploadParams {
+    // Durée de vie des fichiers envoyés, ex: "24h", "7d", "never"
+    ttl: Option<String>,
+    // Stratégie si le nom existe déjà, ex: "number", "reject"
+    conflict: Option<String>,
+    description: Option<String>,
+    // Tags séparés par des virgules
+    tags: Option<String>,
+    // Somme de contrôle attendue, ex: "sha256:<hex>" ou "blake3:<hex>"
+    checksum: Option<String>,
+}
+
 #[get("/")]
-async fn index() -> impl Responder {
-    let config = Config::from_env();
-    let local_ips = get_local_ips();
-    let files = get_uploaded_files(&config.upload_dir);
-    
-    let html = generate_html(&local_ips, config.port, config.refresh_interval, &files, config.max_file_size);
-    
-    HttpResponse::Ok()
+async fn index(req: HttpRequest) -> Result<HttpResponse> {
+    let validators = cache::Validators::for_index();
+    if validators.not_modified(&req) {
+        return Ok(validators.not_modified_response(cache::REVALIDATE));
+    }
+
+    // Listage du stockage et calcul de l'espace occupé : hors des workers actix
+    let html = web::block(|| {
+        let config = Config::from_env();
+        let local_ips = get_local_ips();
+        let files = get_uploaded_files(&config.upload_dir);
+        let pending = scan::pending_files(&config.upload_dir);
+        let quarantined = scan::quarantined_count(&config.upload_dir);
+        let usage = config.quota.usage(&config.upload_dir);
+        let trash_entries = trash::list(&config.upload_dir);
+        let file_versions: HashMap<String, Vec<VersionEntry>> = files
+            .iter()
+            .map(|f| (f.id.clone(), versions::list(&config.upload_dir, &f.id)))
+            .filter(|(_, history)| !history.is_empty())
+            .collect();
+        let secure_shares = config.secure.enabled.then(|| secure::active_count(&config.upload_dir));
+
+        generate_html(&local_ips, config.port, config.refresh_interval, &files, &file_versions, &pending, quarantined, &trash_entries, &config.trash, &usage, config.retention.default_ttl, config.conflict_policy, config.max_file_size, &config.upload_policy, &config.secure, secure_shares)
+    })
+    .await?;
+
+    let mut response = HttpResponse::Ok();
+    validators.apply(&mut response);
+    Ok(response
         .content_type("text/html; charset=utf-8")
+        .insert_header((actix_web::http::header::CACHE_CONTROL, cache::REVALIDATE))
         .insert_header(("X-Content-Type-Options", "nosniff"))
         .insert_header(("X-Frame-Options", "SAMEORIGIN"))
-        .body(html)
+        .body(html))
 }
 
 #[post("/upload")]
-async fn upload_file(mut payload: Multipart) -> Result<HttpResponse> {
+async fn upload_file(req: HttpRequest, mut payload: Multipart) -> Result<HttpResponse> {
     let config = Config::from_env();
     
     // Créer le dossier d'upload s'il n'existe pas
-    fs::create_dir_all(&config.upload_dir).map_err(|e| {
-        eprintln!("❌ Erreur création dossier upload: {}", e);
+    fs::create_dir_all(PathBuf::from(&config.upload_dir).join(STAGING_DIR)).map_err(|e| {
+        error!(error = %e, "❌ Erreur création dossier upload");
         actix_web::error::ErrorInternalServerError("Erreur serveur")
     })?;
 
+    // Vérifier le quota et l'espace disque avant de recevoir quoi que ce soit
+    let quota = config.quota.clone();
+    let upload_dir = config.upload_dir.clone();
+    let budget = web::block(move || quota.remaining(&upload_dir)).await?;
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > budget) {
+        warn!(requested = content_length.unwrap_or(0), available = budget, "💾 Upload refusé: espace insuffisant");
+        metrics::transfer_failed(Direction::Upload, "espace insuffisant", 0);
+        return Ok(insufficient_storage());
+    }
+    let mut received = 0u64;
+    let transfer = match Transfer::start(&req, Direction::Upload) {
+        Ok(transfer) => transfer,
+        Err(response) => return Ok(response),
+    };
+
+    // Les options peuvent venir de l'URL ou de champs de formulaire placés avant les fichiers
+    let params = web::Query::<UploadParams>::from_query(req.query_string())
+        .map(|params| params.into_inner())
+        .unwrap_or(UploadParams { ttl: None, conflict: None, description: None, tags: None, checksum: None });
+    let mut ttl = params.ttl;
+    if let Err(response) = check_ttl(&config.retention, ttl.as_deref()) {
+        return Ok(response);
+    }
+    let mut description = params.description.as_deref().and_then(meta::parse_description);
+    let mut tags = params.tags.as_deref().map(meta::parse_tags).unwrap_or_default();
+    let uploader = access::client_address(&req);
+    let mut conflict_policy = match params.conflict.as_deref() {
+        Some(value) => match parse_conflict_policy(value) {
+            Ok(policy) => policy,
+            Err(response) => return Ok(response),
+        },
+        None => config.conflict_policy,
+    };
+    // Une somme attendue ne vaut que pour le fichier qui la suit
+    let mut expected = match params.checksum.as_deref() {
+        Some(value) => match parse_checksum(value) {
+            Ok(expected) => Some(expected),
+            Err(response) => return Ok(response),
+        },
+        None => None,
+    };
+    let mut results = Vec::new();
+
     while let Some(mut field) = payload.try_next().await? {
         let content_disposition = field.content_disposition();
-        
-        if let Some(filename) = content_disposition.get_filename() {
-            // Sécuriser le nom de fichier
-            let safe_filename = sanitize_filename(filename);
-            let filepath = PathBuf::from(&config.upload_dir).join(&safe_filename);
-            
-            // Éviter l'écrasement en ajoutant un timestamp
-            let final_path = if filepath.exists() {
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs();
-                let stem = filepath.file_stem().unwrap().to_string_lossy();
-                let extension = filepath.extension().map_or(String::new(), |e| format!(".{}", e.to_string_lossy()));
-                PathBuf::from(&config.upload_dir).join(format!("{}_{}{}", stem, timestamp, extension))
-            } else {
-                filepath
-            };
 
-            let mut f = web::block(move || std::fs::File::create(final_path))
-                .await??;
+        let Some(safe_filename) = names::upload_name(content_disposition) else {
+            match content_disposition.get_name() {
+                Some("ttl") => {
+                    let value = read_text_field(&mut field, 256).await?;
+                    if let Err(response) = check_ttl(&config.retention, Some(&value)) {
+                        return Ok(response);
+                    }
+                    ttl = Some(value);
+                }
+                Some("description") => {
+                    description = meta::parse_description(&read_text_field(&mut field, meta::MAX_DESCRIPTION_LEN * 4).await?);
+                }
+                Some("tags") => tags = meta::parse_tags(&read_text_field(&mut field, 2048).await?),
+                Some("checksum") => {
+                    let value = read_text_field(&mut field, 256).await?;
+                    if !value.is_empty() {
+                        expected = match parse_checksum(&value) {
+                            Ok(checksum) => Some(checksum),
+                            Err(response) => return Ok(response),
+                        };
+                    }
+                }
+                Some("conflict") => {
+                    let value = read_text_field(&mut field, 256).await?;
+                    if !value.is_empty() {
+                        conflict_policy = match parse_conflict_policy(&value) {
+                            Ok(policy) => policy,
+                            Err(response) => return Ok(response),
+                        };
+                    }
+                }
+                _ => {}
+            }
+            continue;
+        };
+        let started = Instant::now();
+
+        // Refuser tout de suite plutôt qu'après avoir reçu tout le fichier
+        let (upload_dir, name) = (config.upload_dir.clone(), safe_filename.clone());
+        if conflict_policy == ConflictPolicy::Reject && web::block(move || names::find(&upload_dir, &name)).await?.is_some() {
+            audit_refused(&config.upload_dir, &uploader, &safe_filename, "conflit de nom");
+            return Ok(name_conflict(&safe_filename));
+        }
+
+        // Lire le début du fichier pour identifier son type réel
+        let mut head = Vec::new();
+        let mut finished = false;
+        while head.len() < SNIFF_LEN {
+            match field.try_next().await? {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => {
+                    finished = true;
+                    break;
+                }
+            }
+        }
+
+        let declared = FileKind::from_name(&safe_filename);
+        let detected = FileKind::from_content(&head);
+        if let Err(e) = config.upload_policy.check(&declared, detected.as_ref()) {
+            warn!(file = %safe_filename, reason = %e.message(), "⛔ Upload refusé");
+            audit_refused(&config.upload_dir, &uploader, &safe_filename, "type refusé");
+            return Ok(HttpResponse::UnsupportedMediaType().json(serde_json::json!({
+                "error": e.message(),
+                "file": safe_filename,
+            })));
+        }
+
+        let max_size = config.upload_policy.size_limit(&declared, detected.as_ref(), config.max_file_size);
+        let too_large = || HttpResponse::PayloadTooLarge().json(serde_json::json!({
+            "error": format!("Fichier trop volumineux (max: {} MB)", max_size / (1024 * 1024)),
+            "file": safe_filename,
+        }));
+        if head.len() > max_size {
+            audit_refused(&config.upload_dir, &uploader, &safe_filename, "trop volumineux");
+            return Ok(too_large());
+        }
+        received += head.len() as u64;
+        if received > budget {
+            audit_refused(&config.upload_dir, &uploader, &safe_filename, "espace insuffisant");
+            return Ok(insufficient_storage());
+        }
+
+        // Écrire dans le dossier de staging : un upload interrompu ne laisse
+        // jamais de fichier partiel dans la liste partagée
+        let staged_path = staging_path(&config.upload_dir);
+        let cleanup_path = staged_path.clone();
+        // Chiffré dès l'écriture si le chiffrement au repos est actif
+        let mut f = web::block(move || StagedWriter::create(&staged_path))
+            .await??;
 
-            let mut total_size = 0usize;
+        // Empreintes calculées pendant la réception, sans relire le fichier
+        let expected = expected.take();
+        let mut hasher = integrity::Hasher::new(config.checksum.blake3 || expected.as_ref().is_some_and(Expected::needs_blake3));
+        hasher.update(&head);
+        transfer.pace(head.len()).await;
+        let mut total_size = head.len();
+        f = match web::block(move || f.write_all(&head).map(|_| f)).await? {
+            Ok(f) => f,
+            Err(e) => return write_failed(&cleanup_path, &safe_filename, e),
+        };
+        if !finished {
             while let Some(chunk) = field.try_next().await? {
                 total_size += chunk.len();
-                if total_size > config.max_file_size {
-                    return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-                        "error": format!("Fichier trop volumineux (max: {} MB)", config.max_file_size / (1024 * 1024))
-                    })));
+                received += chunk.len() as u64;
+                if total_size > max_size || received > budget {
+                    // Ne pas laisser de fichier partiel sur le disque
+                    drop(f);
+                    let _ = fs::remove_file(&cleanup_path);
+                    if total_size > max_size {
+                        audit_refused(&config.upload_dir, &uploader, &safe_filename, "trop volumineux");
+                        return Ok(too_large());
+                    }
+                    audit_refused(&config.upload_dir, &uploader, &safe_filename, "espace insuffisant");
+                    return Ok(insufficient_storage());
                 }
-                f = web::block(move || f.write_all(&chunk).map(|_| f)).await??;
+                hasher.update(&chunk);
+                transfer.pace(chunk.len()).await;
+                f = match web::block(move || f.write_all(&chunk).map(|_| f)).await? {
+                    Ok(f) => f,
+                    Err(e) => return write_failed(&cleanup_path, &safe_filename, e),
+                };
             }
+        }
+        if let Err(e) = web::block(move || f.finish()).await? {
+            return write_failed(&cleanup_path, &safe_filename, e);
+        }
+        let checksums = hasher.finalize();
+        if let Some(expected) = expected.filter(|expected| !expected.matches(&checksums)) {
+            let _ = fs::remove_file(&cleanup_path);
+            warn!(file = %safe_filename, size = total_size, "⛔ Upload refusé: somme de contrôle incorrecte");
+            audit_refused(&config.upload_dir, &uploader, &safe_filename, "somme de contrôle incorrecte");
+            return Ok(HttpResponse::UnprocessableEntity().json(serde_json::json!({
+                "error": "Somme de contrôle incorrecte, le fichier a été altéré pendant le transfert",
+                "file": safe_filename,
+                "expected": expected.describe(),
+                "sha256": checksums.sha256,
+            })));
+        }
+
+        let expires_at = match check_ttl(&config.retention, ttl.as_deref()) {
+            Ok(expires_at) => expires_at,
+            Err(response) => {
+                let _ = fs::remove_file(&cleanup_path);
+                return Ok(response);
+            }
+        };
+        let file_meta = FileMeta {
+            name: None,
+            expires_at,
+            uploaded_at: Some(
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs(),
+            ),
+            uploader: Some(uploader.clone()),
+            sha256: Some(checksums.sha256),
+            blake3: checksums.blake3,
+            verified_at: None,
+            corrupt: false,
+            mime: Some(detected.map_or(declared.mime, |kind| kind.mime)),
+            description: description.clone(),
+            tags: tags.clone(),
+        };
 
-            println!("📁 Fichier uploadé: {} ({} bytes)", safe_filename, total_size);
+        if config.scan.is_enabled() {
+            scan::submit(&config.upload_dir, &config.scan, &cleanup_path, &safe_filename, &file_meta, conflict_policy).map_err(|e| {
+                error!(file = %safe_filename, error = %e, "❌ Erreur mise en attente d'analyse");
+                let _ = fs::remove_file(&cleanup_path);
+                actix_web::error::ErrorInternalServerError("Erreur serveur")
+            })?;
+            info!(file = %safe_filename, size = total_size, duration_ms = started.elapsed().as_millis() as u64, "🔍 Fichier uploadé, analyse en cours");
+            audit::record(&config.upload_dir, audit::Entry::new(audit::Event::Upload, &uploader, &safe_filename)
+                .size(total_size as u64)
+                .detail("en attente d'analyse"));
+            metrics::transfer_completed(Direction::Upload, total_size as u64, started.elapsed());
+            results.push(serde_json::json!({
+                "file": safe_filename,
+                "id": null,
+                "stored_as": null,
+                "status": "scanning",
+            }));
+        } else {
+            let upload_dir = config.upload_dir.clone();
+            let filename = safe_filename.clone();
+            let max_versions = config.versioning.max_versions;
+            let dedup = config.dedup.enabled;
+            let published = web::block(move || {
+                publish_upload(&upload_dir, &cleanup_path, &filename, &file_meta, conflict_policy, max_versions, dedup)
+                    .inspect_err(|_| {
+                        let _ = fs::remove_file(&cleanup_path);
+                    })
+            })
+            .await?;
+            let published = match published {
+                Ok(published) => published,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    audit_refused(&config.upload_dir, &uploader, &safe_filename, "conflit de nom");
+                    return Ok(name_conflict(&safe_filename));
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if published.duplicate {
+                info!(file = %safe_filename, stored_as = %published.stored_as, size = total_size, duration_ms = started.elapsed().as_millis() as u64, "♊ Fichier identique déjà présent");
+            } else if let Some(existing) = &published.existing {
+                info!(file = %safe_filename, stored_as = %published.stored_as, same_as = %existing, size = total_size, duration_ms = started.elapsed().as_millis() as u64, "♊ Fichier uploadé, contenu déjà présent");
+            } else {
+                info!(file = %safe_filename, stored_as = %published.stored_as, size = total_size, duration_ms = started.elapsed().as_millis() as u64, "📁 Fichier uploadé");
+            }
+            audit_published(&config.upload_dir, &uploader, &safe_filename, &published, total_size as u64);
+            if !published.duplicate {
+                let (upload_dir, id) = (config.upload_dir.clone(), published.id.clone());
+                if let Some(file) = web::block(move || webhook::lookup(webhook::Event::Upload, &upload_dir, &id)).await? {
+                    webhook::notify(webhook::Event::Upload, &uploader, &file, None);
+                }
+            }
+            metrics::transfer_completed(Direction::Upload, total_size as u64, started.elapsed());
+            results.push(serde_json::json!({
+                "file": safe_filename,
+                "id": published.id,
+                "stored_as": published.stored_as,
+                "existing": published.existing,
+                "status": if published.duplicate { "duplicate" } else { "stored" },
+            }));
         }
     }
 
+    // Les clients API (et l'interface) reçoivent les noms finaux en JSON
+    if accepts_json(&req) {
+        return Ok(HttpResponse::Ok().json(serde_json::json!({ "files": results })));
+    }
+
     Ok(HttpResponse::Found()
         .insert_header(("Location", "/"))
         .finish())
 }
 
-#[get("/download/{filename}")]
-async fn download_file(path: web::Path<String>) -> Result<HttpResponse> {
+// Upload refusé : journalisé et compté avec sa raison
+fn audit_refused(upload_dir: &str, uploader: &str, filename: &str, reason: &str) {
+    audit::record(upload_dir, audit::Entry::new(audit::Event::Upload, uploader, filename).failed(reason));
+    metrics::transfer_failed(Direction::Upload, reason, 0);
+}
+
+// Upload publié, et renommage si un conflit de nom a imposé un autre nom
+fn audit_published(upload_dir: &str, uploader: &str, filename: &str, published: &Published, size: u64) {
+    let mut entry = audit::Entry::new(audit::Event::Upload, uploader, &published.stored_as)
+        .id(&published.id)
+        .size(size);
+    if published.duplicate {
+        entry = entry.detail(&format!("identique à {}", published.stored_as));
+    }
+    audit::record(upload_dir, entry);
+    if !published.duplicate && published.stored_as != filename {
+        audit::record(upload_dir, audit::Entry::new(audit::Event::Rename, uploader, &published.stored_as)
+            .id(&published.id)
+            .detail(&format!("{} → {} (conflit de nom)", filename, published.stored_as)));
+    }
+}
+
+fn accepts_json(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"))
+}
+
+fn parse_conflict_policy(value: &str) -> std::result::Result<ConflictPolicy, HttpResponse> {
+    ConflictPolicy::parse(value).ok_or_else(|| {
+        HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Stratégie de conflit inconnue: {} (timestamp, number, hash, version, overwrite, reject)", value)
+        }))
+    })
+}
+
+// Date d'expiration demandée, vérifiée dès réception du paramètre : une durée
+// invalide est refusée avant de recevoir les fichiers qu'elle concerne
+fn check_ttl(retention: &RetentionConfig, value: Option<&str>) -> std::result::Result<Option<u64>, HttpResponse> {
+    retention.expiry_for(value).map_err(|error| {
+        HttpResponse::BadRequest().json(serde_json::json!({ "error": error }))
+    })
+}
+
+fn parse_checksum(value: &str) -> std::result::Result<Expected, HttpResponse> {
+    Expected::parse(value).ok_or_else(|| {
+        HttpResponse::BadRequest().json(serde_json::json!({
+            "error": format!("Somme de contrôle invalide: {} (sha256:<hex>, blake3:<hex>)", value)
+        }))
+    })
+}
+
+fn name_conflict(filename: &str) -> HttpResponse {
+    HttpResponse::Conflict().json(serde_json::json!({
+        "error": conflict::conflict_error(filename).to_string(),
+        "file": filename,
+    }))
+}
+
+async fn read_text_field(field: &mut actix_multipart::Field, max_len: usize) -> Result<String> {
+    let mut value = Vec::new();
+    while let Some(chunk) = field.try_next().await? {
+        value.extend_from_slice(&chunk);
+        if value.len() > max_len {
+            return Err(actix_web::error::ErrorBadRequest("Champ de formulaire trop long"));
+        }
+    }
+    Ok(String::from_utf8_lossy(&value).trim().to_string())
+}
+
+// Disque plein ou erreur d'écriture : ne rien laisser derrière
+fn write_failed(staged_path: &Path, filename: &str, e: std::io::Error) -> Result<HttpResponse> {
+    let _ = fs::remove_file(staged_path);
+    error!(file = %filename, error = %e, "❌ Erreur écriture fichier");
+    if e.kind() == std::io::ErrorKind::StorageFull {
+        return Ok(insufficient_storage());
+    }
+    Err(e.into())
+}
+
+fn insufficient_storage() -> HttpResponse {
+    HttpResponse::InsufficientStorage().json(serde_json::json!({
+        "error": "Espace de stockage insuffisant sur le serveur"
+    }))
+}
+
+fn staging_path(upload_dir: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    PathBuf::from(upload_dir)
+        .join(STAGING_DIR)
+        .join(nanos.to_string())
+}
+
+struct Published {
+    id: String,
+    // Nom sous lequel le fichier est partagé
+    stored_as: String,
+    // Le même contenu existait déjà, rien n'a été ajouté
+    duplicate: bool,
+    // Nom d'un autre fichier partagé ayant le même contenu
+    existing: Option<String>,
+}
+
+// Publier un fichier complet dans le dossier partagé avec ses métadonnées,
+// en appliquant la stratégie choisie si le nom est déjà pris
+fn publish_upload(upload_dir: &str, staged: &Path, filename: &str, file_meta: &FileMeta, policy: ConflictPolicy, max_versions: usize, dedup: bool) -> std::io::Result<Published> {
+    let (id, name, previous) = match conflict::resolve(upload_dir, staged, filename, policy)? {
+        Target::New(name) => (names::new_id(upload_dir), name, None),
+        Target::Replace(id) => {
+            let previous = meta::load(upload_dir, &id);
+            if policy == ConflictPolicy::Version {
+                versions::archive_current(upload_dir, &id, max_versions)?;
+            }
+            (id, filename.to_string(), Some(previous))
+        }
+        Target::Duplicate { id, name } => {
+            fs::remove_file(staged)?;
+            return Ok(Published {
+                id,
+                stored_as: name,
+                duplicate: true,
+                existing: None,
+            });
+        }
+    };
+    let existing = file_meta
+        .sha256
+        .as_deref()
+        .and_then(|sha256| dedup::existing_copy(upload_dir, sha256, Some(&id)));
+
+    // Les métadonnées portent le nom affiché : elles doivent exister avant le fichier
+    let file_meta = FileMeta {
+        name: Some(name.clone()),
+        ..file_meta.clone()
+    };
+    meta::save(upload_dir, &id, &file_meta)?;
+    let size = fs::metadata(staged)?.len();
+    let placed = match file_meta.sha256.as_deref() {
+        Some(sha256) if dedup => dedup::place(upload_dir, staged, &PathBuf::from(upload_dir).join(&id), sha256),
+        _ => storage::open(upload_dir).put_file(&id, staged),
+    };
+    if let Err(e) = placed {
+        match &previous {
+            Some(previous) => meta::save(upload_dir, &id, previous)?,
+            None => meta::remove(upload_dir, &id),
+        }
+        return Err(e);
+    }
+    // Le contenu remplacé n'est peut-être plus référencé nulle part
+    if policy == ConflictPolicy::Overwrite && previous.is_some() {
+        dedup::release(upload_dir, previous.and_then(|previous| previous.sha256).as_deref());
+        quota::released();
+    } else if !(dedup && existing.is_some()) {
+        quota::stored(size);
+    }
+    Ok(Published {
+        id,
+        stored_as: name,
+        duplicate: false,
+        existing,
+    })
+}
+
+#[get("/download/{id}")]
+async fn download_file(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
     let config = Config::from_env();
-    let filename = path.into_inner();
-    let safe_filename = sanitize_filename(&filename);
-    let filepath = PathBuf::from(&config.upload_dir).join(&safe_filename);
+    let id = path.into_inner();
 
-    if !filepath.exists() {
+    if !names::valid_id(&id) {
         return Ok(HttpResponse::NotFound().json(serde_json::json!({
             "error": "Fichier non trouvé"
         })));
     }
 
-    let file_data = web::block(move || std::fs::read(filepath)).await??;
-    
-    let content_disposition = ContentDisposition {
-        disposition: DispositionType::Attachment,
-        parameters: vec![DispositionParam::Filename(safe_filename)],
+    let file_meta = meta::load(&config.upload_dir, &id);
+    let filename = file_meta.name.clone().unwrap_or_else(|| id.clone());
+    // Variante précompressée (<nom>.br, <nom>.gz) servie à la place si le client l'accepte
+    let upload_dir = config.upload_dir.clone();
+    let (file_id, name, original) = (id.clone(), filename.clone(), file_meta.clone());
+    let accepted = compress::accepted_variants(&req);
+    let variant = web::block(move || compress::precompressed(&upload_dir, &file_id, &name, &original, &accepted)).await?;
+    let response = match variant {
+        Some(variant) => {
+            let mut response = storage::download(&req, &config.upload_dir, &variant.id, &filename, &variant.meta).await?;
+            variant.apply(&mut response);
+            Ok(response)
+        }
+        None => storage::download(&req, &config.upload_dir, &id, &filename, &file_meta).await,
     };
-
-    Ok(HttpResponse::Ok()
-        .insert_header(content_disposition)
-        .body(file_data))
+    if let Ok(response) = &response {
+        audit::download(&req, &config.upload_dir, &id, &filename, response);
+    }
+    response
 }
 
-#[post("/delete/{filename}")]
-async fn delete_file(path: web::Path<String>) -> Result<HttpResponse> {
+#[post("/delete/{id}")]
+async fn delete_file(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
     let config = Config::from_env();
-    let filename = path.into_inner();
-    let safe_filename = sanitize_filename(&filename);
-    let filepath = PathBuf::from(&config.upload_dir).join(&safe_filename);
+    let id = path.into_inner();
+    if !names::valid_id(&id) {
+        return Ok(HttpResponse::NotFound().json(serde_json::json!({
+            "error": "Fichier non trouvé"
+        })));
+    }
+    let deleted_by = access::client_address(&req);
+    // Lu avant qu'il ne quitte le dossier partagé
+    let file = webhook::lookup(webhook::Event::Delete, &config.upload_dir, &id);
 
-    match std::fs::remove_file(&filepath) {
-        Ok(_) => {
-            println!("🗑️ Fichier supprimé: {}", safe_filename);
+    // Le fichier part en corbeille, il reste restaurable jusqu'à la purge
+    match trash::move_to_trash(&config.upload_dir, &id, &deleted_by) {
+        Ok(entry) => {
+            info!(file = %entry.name, size = entry.size, "🗑️ Fichier placé dans la corbeille");
+            audit::record(&config.upload_dir, audit::Entry::new(audit::Event::Delete, &deleted_by, &entry.name)
+                .id(&id)
+                .size(entry.size)
+                .detail("corbeille"));
+            if let Some(file) = file {
+                webhook::notify(webhook::Event::Delete, &deleted_by, &file, Some("corbeille"));
+            }
             Ok(HttpResponse::Found()
                 .insert_header(("Location", "/"))
                 .finish())
         }
         Err(e) => {
-            eprintln!("❌ Erreur suppression fichier: {}", e);
+            error!(id = %id, error = %e, "❌ Erreur suppression fichier");
+            let name = names::display_name(&config.upload_dir, &id);
+            let reason = if e.kind() == std::io::ErrorKind::NotFound { "introuvable" } else { "erreur" };
+            audit::record(&config.upload_dir, audit::Entry::new(audit::Event::Delete, &deleted_by, &name).id(&id).failed(reason));
             Ok(HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": "Erreur lors de la suppression"
             })))
@@ -168,8 +749,21 @@ async fn delete_file(path: web::Path<String>) -> Result<HttpResponse> {
     }
 }
 
-fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files: &[FileInfo], max_file_size: usize) -> String {
+#[allow(clippy::too_many_arguments)]
+fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files: &[FileInfo], file_versions: &HashMap<String, Vec<VersionEntry>>, pending: &[FileInfo], quarantined: usize, trash_entries: &[TrashEntry], trash: &TrashConfig, usage: &StorageUsage, default_ttl: Option<u64>, conflict_policy: ConflictPolicy, max_file_size: usize, policy: &UploadPolicy, secure: &SecureConfig, secure_shares: Option<usize>) -> String {
     let max_size_mb = max_file_size / (1024 * 1024);
+    let conflict_options: String = ConflictPolicy::ALL
+        .iter()
+        .map(|p| format!(r#"<option value="{}">{}</option>"#, p.as_str(), p.label()))
+        .collect();
+    let policy_info = format_policy_info(policy);
+    let usage_meter = format_usage_meter(usage);
+    let default_ttl = default_ttl.map_or("illimitée".to_string(), retention::format_duration);
+    let secure_tab = if secure_shares.is_some() {
+        r#"<button class="tab" onclick="showTab('secure')">🔒 Chiffré</button>"#
+    } else {
+        ""
+    };
     
     let mut html = format!(r#"
     <!DOCTYPE html>
@@ -218,6 +812,30 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 background-clip: text;
             }}
             
+            .usage-meter {{
+                max-width: 400px;
+                margin: 15px auto 0;
+                font-size: 0.9em;
+            }}
+            
+            .usage-bar {{
+                height: 8px;
+                background: rgba(255, 255, 255, 0.3);
+                border-radius: 4px;
+                margin-top: 6px;
+                overflow: hidden;
+            }}
+            
+            .usage-fill {{
+                height: 100%;
+                background: linear-gradient(90deg, #4ecdc4, #44a08d);
+                border-radius: 4px;
+            }}
+            
+            .usage-fill.high {{
+                background: linear-gradient(90deg, #ff6b6b, #ee5a52);
+            }}
+            
             .tabs {{
                 display: flex;
                 justify-content: center;
@@ -249,6 +867,38 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 display: block;
             }}
             
+            .ttl-select {{
+                text-align: center;
+                margin-bottom: 15px;
+            }}
+            
+            .ttl-select select {{
+                background: rgba(255, 255, 255, 0.2);
+                color: white;
+                border: none;
+                border-radius: 8px;
+                padding: 6px 10px;
+                margin-left: 8px;
+            }}
+            
+            .ttl-select option {{
+                color: black;
+            }}
+            
+            .ttl-select input {{
+                background: rgba(255, 255, 255, 0.2);
+                color: white;
+                border: none;
+                border-radius: 8px;
+                padding: 6px 10px;
+                margin: 8px 4px 0;
+                width: 260px;
+            }}
+            
+            .ttl-select input::placeholder {{
+                color: rgba(255, 255, 255, 0.7);
+            }}
+            
             .upload-area {{
                 background: rgba(255, 255, 255, 0.15);
                 border: 3px dashed rgba(255, 255, 255, 0.5);
@@ -304,6 +954,16 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 box-shadow: 0 15px 35px rgba(0, 0, 0, 0.3);
             }}
             
+            .file-card.scanning {{
+                opacity: 0.7;
+                border: 2px dashed rgba(255, 255, 255, 0.5);
+            }}
+            
+            .scan-status {{
+                font-weight: bold;
+                color: #ffd166;
+            }}
+            
             .file-icon {{
                 font-size: 3em;
                 text-align: center;
@@ -322,6 +982,39 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 margin-bottom: 15px;
             }}
             
+            .file-description {{
+                font-size: 0.9em;
+                font-style: italic;
+                margin-bottom: 10px;
+                word-break: break-word;
+            }}
+            
+            .file-tags {{
+                margin-bottom: 10px;
+            }}
+            
+            .file-hash {{
+                font-family: monospace;
+                font-size: 0.75em;
+                opacity: 0.8;
+                margin-bottom: 10px;
+            }}
+            
+            .file-hash.corrupt {{
+                color: #ff6b6b;
+                font-weight: bold;
+                opacity: 1;
+            }}
+            
+            .tag {{
+                display: inline-block;
+                background: rgba(255, 255, 255, 0.25);
+                border-radius: 10px;
+                padding: 2px 8px;
+                margin: 2px;
+                font-size: 0.8em;
+            }}
+            
             .file-actions {{
                 display: flex;
                 gap: 10px;
@@ -347,10 +1040,41 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 color: white;
             }}
             
+            .btn-edit {{
+                flex: 0 0 auto;
+                background: rgba(255, 255, 255, 0.25);
+                color: white;
+            }}
+            
             .btn:hover {{
                 transform: scale(1.05);
             }}
             
+            .versions {{
+                margin-top: 15px;
+                font-size: 0.85em;
+            }}
+            
+            .versions summary {{
+                cursor: pointer;
+                opacity: 0.9;
+            }}
+            
+            .versions ul {{
+                list-style: none;
+                margin-top: 8px;
+            }}
+            
+            .versions li {{
+                padding: 4px 0;
+                border-bottom: 1px solid rgba(255, 255, 255, 0.2);
+            }}
+            
+            .versions a {{
+                text-decoration: none;
+                margin-left: 6px;
+            }}
+            
             .qr-grid {{
                 display: grid;
                 grid-template-columns: repeat(auto-fit, minmax(250px, 1fr));
@@ -388,6 +1112,14 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 backdrop-filter: blur(10px);
             }}
             
+            .status-bar.degraded {{
+                background: rgba(255, 152, 0, 0.9);
+            }}
+            
+            .status-bar.down {{
+                background: rgba(244, 67, 54, 0.9);
+            }}
+            
             .progress-bar {{
                 width: 100%;
                 height: 6px;
@@ -428,43 +1160,123 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 opacity: 0.7;
             }}
             
+            .policy-info {{
+                font-size: 0.85em;
+                opacity: 0.8;
+                margin-top: 8px;
+            }}
+            
+            .upload-error {{
+                display: none;
+                background: rgba(255, 107, 107, 0.9);
+                padding: 12px 20px;
+                border-radius: 10px;
+                margin-bottom: 20px;
+                font-weight: bold;
+            }}
+            
+            .upload-notice {{
+                display: none;
+                background: rgba(78, 205, 196, 0.9);
+                padding: 12px 20px;
+                border-radius: 10px;
+                margin-bottom: 20px;
+                font-weight: bold;
+            }}
+            
             .empty-state .icon {{
                 font-size: 4em;
                 margin-bottom: 20px;
             }}
+            
+            .secure-link {{
+                width: 100%;
+                background: rgba(255, 255, 255, 0.2);
+                color: white;
+                border: none;
+                border-radius: 8px;
+                padding: 8px;
+                margin: 10px 0;
+            }}
+            
+            .secure-list li {{
+                padding: 8px 0;
+                border-bottom: 1px solid rgba(255, 255, 255, 0.2);
+                list-style: none;
+            }}
+            
+            .secure-list a {{
+                color: white;
+            }}
+            
+            .secure-list .btn {{
+                padding: 4px 8px;
+                margin-left: 8px;
+            }}
         </style>
     </head>
     <body>
-        <div class="status-bar" id="status">🟢 Serveur actif</div>
+        <div class="status-bar" id="status"><span id="health">⏳ Vérification…</span> · <span id="refresh"></span></div>
         
         <div class="container">
             <div class="header">
                 <h1>📁 Serveur de Partage</h1>
                 <p>Partagez vos fichiers facilement sur le réseau local</p>
+                {}
             </div>
             
             <div class="tabs">
                 <button class="tab active" onclick="showTab('files')">📁 Fichiers</button>
+                <button class="tab" onclick="showTab('trash')">🗑️ Corbeille ({})</button>
+                {}
                 <button class="tab" onclick="showTab('qr')">📱 QR Codes</button>
             </div>
             
             <div id="files" class="tab-content active">
                 <form action="/upload" method="post" enctype="multipart/form-data" id="uploadForm">
+                    <div class="ttl-select">
+                        <label for="ttlSelect">⏳ Durée de conservation :</label>
+                        <select id="ttlSelect" name="ttl">
+                            <option value="">Par défaut ({})</option>
+                            <option value="1h">1 heure</option>
+                            <option value="1d">1 jour</option>
+                            <option value="7d">7 jours</option>
+                            <option value="30d">30 jours</option>
+                            <option value="never">Illimitée</option>
+                        </select>
+                        <label for="conflictSelect">🔀 Si le nom existe :</label>
+                        <select id="conflictSelect" name="conflict">
+                            <option value="">Par défaut ({})</option>
+                            {}
+                        </select>
+                        <br>
+                        <input type="text" id="descriptionInput" name="description" maxlength="1000" placeholder="📝 Description (optionnelle)">
+                        <input type="text" id="tagsInput" name="tags" placeholder="🏷️ Tags, séparés par des virgules">
+                    </div>
                     <div class="upload-area" id="uploadArea">
                         <div style="font-size: 3em; margin-bottom: 20px;">☁️</div>
                         <h3>Glissez vos fichiers ici ou cliquez pour sélectionner</h3>
                         <p>Taille maximale: {} MB par fichier</p>
-                        <input type="file" id="fileInput" name="file" multiple accept="*/*">
+                        {}
+                        <input type="file" id="fileInput" name="file" multiple accept="{}">
                         <div class="progress-bar" id="progressBar" style="display: none;">
                             <div class="progress" id="progress"></div>
                         </div>
                     </div>
                 </form>
+                <div class="upload-error" id="uploadError"></div>
+                <div class="upload-notice" id="uploadNotice"></div>
                 
                 <h2>📋 Fichiers disponibles ({})</h2>
-    "#, max_size_mb, files.len());
+    "#, usage_meter, trash_entries.len(), secure_tab, default_ttl, conflict_policy.label(), conflict_options, max_size_mb, policy_info, policy.accept_attribute(), files.len());
+
+    if quarantined > 0 {
+        html.push_str(&format!(r#"
+            <p class="policy-info">🛡️ {} fichier(s) bloqué(s) par l'analyse antivirus et placé(s) en quarantaine</p>
+        "#, quarantined));
+    }
 
-    if files.is_empty() {
+    if files.is_empty() && pending.is_empty() {
         html.push_str(r#"
             <div class="empty-state">
                 <div class="icon">📭</div>
@@ -474,19 +1286,42 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
         "#);
     } else {
         html.push_str(r#"<div class="files-grid">"#);
+
+        for file in pending {
+            html.push_str(&format!(r#"
+                <div class="file-card scanning">
+                    <div class="file-icon">{}</div>
+                    <div class="file-name">{}</div>
+                    <div class="file-info">
+                        📏 {} | 🕒 {}
+                    </div>
+                    <div class="scan-status">🔍 Analyse antivirus en cours…</div>
+                </div>
+            "#, get_file_icon(&file.file_type), escape_html(&file.name), format_file_size(file.size), format_timestamp(file.uploaded_at)));
+        }
         
         for file in files {
             let file_icon = get_file_icon(&file.file_type);
             let file_size = format_file_size(file.size);
             let upload_date = format_timestamp(file.uploaded_at);
+            let lifetime = file
+                .expires_at
+                .map_or(String::new(), |at| format!(" | ⏳ {}", format_remaining(at)));
+            let uploader = file
+                .uploader
+                .as_ref()
+                .map_or(String::new(), |ip| format!(" | 👤 {}", escape_html(ip)));
+            let description = file.description.as_deref().unwrap_or("");
+            let tags = file.tags.join(", ");
             
             html.push_str(&format!(r#"
                 <div class="file-card">
                     <div class="file-icon">{}</div>
                     <div class="file-name">{}</div>
                     <div class="file-info">
-                        📏 {} | 🕒 {}
+                        📏 {} | 🕒 {}{}{}
                     </div>
+                    {}
                     <div class="file-actions">
                         <button class="btn btn-download" onclick="downloadFile('{}')">
                             ⬇️ Télécharger
@@ -494,17 +1329,113 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                         <button class="btn btn-delete" onclick="deleteFile('{}')">
                             🗑️ Supprimer
                         </button>
+                        <button class="btn btn-edit" onclick="editDetails(this)" title="Modifier la description et les tags" data-id="{}" data-description="{}" data-tags="{}">
+                            ✏️
+                        </button>
+                        <button class="btn btn-edit" onclick="verifyFile('{}')" title="Vérifier l'intégrité du fichier">
+                            🧾
+                        </button>
                     </div>
+                    {}
                 </div>
-            "#, file_icon, file.name, file_size, upload_date, file.name, file.name));
+            "#, file_icon, escape_html(&file.name), file_size, upload_date, uploader, lifetime, format_file_details(file), file.id, file.id, file.id, escape_html(description), escape_html(&tags), file.id, format_version_history(&file.id, file_versions.get(&file.id))));
         }
         
         html.push_str(r#"</div>"#);
     }
 
-    html.push_str(r#"
+    html.push_str(&format!(r#"
             </div>
             
+            <div id="trash" class="tab-content">
+                <h2>🗑️ Corbeille ({})</h2>
+                <p class="policy-info">Les éléments de la corbeille sont supprimés définitivement après: {}</p>
+    "#, trash_entries.len(), trash.describe()));
+
+    if trash_entries.is_empty() {
+        html.push_str(r#"
+            <div class="empty-state">
+                <div class="icon">🧹</div>
+                <h3>La corbeille est vide</h3>
+            </div>
+        "#);
+    } else {
+        html.push_str(r#"
+                <div class="file-actions" style="max-width: 300px; margin: 20px 0;">
+                    <button class="btn btn-delete" onclick="emptyTrash()">🔥 Vider la corbeille</button>
+                </div>
+                <div class="files-grid">
+        "#);
+
+        for entry in trash_entries {
+            html.push_str(&format!(r#"
+                <div class="file-card">
+                    <div class="file-icon">{}</div>
+                    <div class="file-name">{}</div>
+                    <div class="file-info">
+                        📏 {} | 🗑️ {} par {}
+                    </div>
+                    <div class="file-actions">
+                        <button class="btn btn-download" onclick="trashAction('{}', 'restore')">
+                            ♻️ Restaurer
+                        </button>
+                        <button class="btn btn-delete" onclick="trashAction('{}', 'purge')">
+                            🔥 Supprimer
+                        </button>
+                    </div>
+                </div>
+            "#, get_file_icon(&entry.file_type), escape_html(&entry.name), format_file_size(entry.size), format_timestamp(entry.deleted_at), escape_html(&entry.deleted_by), entry.id, entry.id));
+        }
+
+        html.push_str(r#"</div>"#);
+    }
+
+    html.push_str(r#"
+            </div>
+    "#);
+
+    if let Some(count) = secure_shares {
+        html.push_str(&format!(r#"
+            <div id="secure" class="tab-content">
+                <h2>🔒 Partage chiffré de bout en bout</h2>
+                <p class="policy-info">Le fichier est chiffré dans votre navigateur avant l'envoi et la clé n'existe que dans le lien : le serveur ne peut lire ni son contenu ni son nom. Partages {} — {} partage(s) actif(s).</p>
+                <div class="ttl-select">
+                    <label for="secureTtl">⏳ Expiration :</label>
+                    <select id="secureTtl">
+                        <option value="">Par défaut</option>
+                        <option value="1h">1 heure</option>
+                        <option value="1d">1 jour</option>
+                        <option value="7d">7 jours</option>
+                    </select>
+                    <label for="secureDownloads">⬇️ Téléchargements :</label>
+                    <select id="secureDownloads">
+                        <option value="1">1</option>
+                        <option value="5">5</option>
+                        <option value="20">20</option>
+                        <option value="100">100</option>
+                    </select>
+                </div>
+                <div class="upload-area" onclick="document.getElementById('secureFile').click()">
+                    <div style="font-size: 3em; margin-bottom: 20px;">🔐</div>
+                    <h3>Cliquez pour choisir le fichier à chiffrer</h3>
+                    <p>Taille maximale: {} MB</p>
+                    <input type="file" id="secureFile" onchange="secureUpload()">
+                </div>
+                <p id="secureStatus"></p>
+                <div class="qr-card" id="secureResult" style="display: none; max-width: 400px; margin: 20px auto;">
+                    <div class="qr-code" id="secureQr"></div>
+                    <input type="text" class="secure-link" id="secureLink" readonly>
+                    <div class="file-actions">
+                        <button class="btn btn-download" onclick="copySecureLink()">📋 Copier le lien</button>
+                    </div>
+                </div>
+                <h3>📤 Mes partages chiffrés</h3>
+                <ul class="secure-list" id="secureList"></ul>
+            </div>
+        "#, secure.describe(), count, max_size_mb));
+    }
+
+    html.push_str(r#"
             <div id="qr" class="tab-content">
                 <div class="ip-list">
                     <h3>🌐 Adresses d'accès disponibles:</h3>
@@ -557,6 +1488,7 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
             </div>
         </div>
         
+        <script src="/secure.js"></script>
         <script>
             // Gestion des onglets
             function showTab(tabName) {{
@@ -577,6 +1509,8 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
             const uploadForm = document.getElementById('uploadForm');
             const progressBar = document.getElementById('progressBar');
             const progress = document.getElementById('progress');
+            const uploadError = document.getElementById('uploadError');
+            const uploadNotice = document.getElementById('uploadNotice');
             
             uploadArea.addEventListener('click', () => fileInput.click());
             
@@ -603,8 +1537,14 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 
                 progressBar.style.display = 'block';
                 progress.style.width = '0%';
+                uploadError.style.display = 'none';
+                uploadNotice.style.display = 'none';
                 
                 const formData = new FormData();
+                formData.append('ttl', document.getElementById('ttlSelect').value);
+                formData.append('conflict', document.getElementById('conflictSelect').value);
+                formData.append('description', document.getElementById('descriptionInput').value);
+                formData.append('tags', document.getElementById('tagsInput').value);
                 for (let file of fileInput.files) {{
                     formData.append('file', file);
                 }}
@@ -620,9 +1560,34 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 
                 xhr.addEventListener('load', () => {{
                     if (xhr.status === 200 || xhr.status === 302) {{
-                        setTimeout(() => location.reload(), 1000);
+                        // Signaler les fichiers enregistrés sous un autre nom
+                        const notices = [];
+                        try {{
+                            for (const f of JSON.parse(xhr.responseText).files || []) {{
+                                if (f.status === 'duplicate') {{
+                                    notices.push(`${{f.file}} : contenu identique déjà présent (${{f.stored_as}})`);
+                                }} else if (f.existing) {{
+                                    notices.push(`${{f.file}} existe déjà sous le nom ${{f.existing}}`);
+                                }} else if (f.stored_as && f.stored_as !== f.file) {{
+                                    notices.push(`${{f.file}} enregistré sous ${{f.stored_as}}`);
+                                }}
+                            }}
+                        }} catch (_) {{}}
+                        if (notices.length > 0) {{
+                            uploadNotice.textContent = 'ℹ️ ' + notices.join(' — ');
+                            uploadNotice.style.display = 'block';
+                        }}
+                        setTimeout(() => location.reload(), notices.length > 0 ? 3000 : 1000);
                     }} else {{
-                        alert('Erreur lors de l\'upload');
+                        let message = 'Erreur lors de l\'upload';
+                        try {{
+                            const body = JSON.parse(xhr.responseText);
+                            if (body.error) {{
+                                message = body.file ? `${{body.file}} : ${{body.error}}` : body.error;
+                            }}
+                        }} catch (_) {{}}
+                        uploadError.textContent = '❌ ' + message;
+                        uploadError.style.display = 'block';
                         progressBar.style.display = 'none';
                     }}
                 }});
@@ -633,90 +1598,295 @@ fn generate_html(local_ips: &[String], port: u16, refresh_interval: u32, files:
                 }});
                 
                 xhr.open('POST', '/upload');
+                xhr.setRequestHeader('Accept', 'application/json');
                 xhr.send(formData);
             }}
             
-            function downloadFile(filename) {{
-                window.location.href = '/download/' + encodeURIComponent(filename);
+            function downloadFile(id) {{
+                window.location.href = '/download/' + encodeURIComponent(id);
+            }}
+            
+            function deleteFile(id) {{
+                if (confirm('Déplacer ce fichier vers la corbeille ?')) {{
+                    fetch('/delete/' + encodeURIComponent(id), {{
+                        method: 'POST'
+                    }}).then(() => location.reload());
+                }}
             }}
             
-            function deleteFile(filename) {{
-                if (confirm('Êtes-vous sûr de vouloir supprimer ce fichier ?')) {{
-                    fetch('/delete/' + encodeURIComponent(filename), {{
+            function restoreVersion(fileId, id) {{
+                if (confirm('Restaurer cette version ? La version actuelle sera conservée dans l\'historique.')) {{
+                    fetch('/versions/' + encodeURIComponent(fileId) + '/' + id + '/restore', {{
                         method: 'POST'
                     }}).then(() => location.reload());
                 }}
             }}
             
+            function editDetails(btn) {{
+                const description = prompt('Description :', btn.dataset.description);
+                if (description === null) return;
+                const tags = prompt('Tags (séparés par des virgules) :', btn.dataset.tags);
+                if (tags === null) return;
+                fetch('/files/' + encodeURIComponent(btn.dataset.id), {{
+                    method: 'POST',
+                    headers: {{ 'Content-Type': 'application/x-www-form-urlencoded' }},
+                    body: new URLSearchParams({{ description, tags }})
+                }}).then(() => location.reload());
+            }}
+            
+            function verifyFile(id) {{
+                fetch('/files/' + encodeURIComponent(id) + '/verify', {{
+                    method: 'POST'
+                }}).then(r => r.json()).then(result => {{
+                    if (result.error) {{
+                        alert('❌ ' + result.error);
+                    }} else if (result.ok) {{
+                        alert('✅ Fichier intact\nSHA-256 : ' + result.sha256);
+                    }} else {{
+                        alert('⚠️ Fichier corrompu !\nAttendu : ' + result.expected_sha256 + '\nObtenu : ' + result.sha256);
+                    }}
+                    location.reload();
+                }});
+            }}
+            
+            function trashAction(id, action) {{
+                if (action === 'purge' && !confirm('Supprimer définitivement ce fichier ?')) return;
+                fetch('/trash/' + id + '/' + action, {{
+                    method: 'POST'
+                }}).then(() => location.reload());
+            }}
+            
+            function emptyTrash() {{
+                if (confirm('Supprimer définitivement tous les éléments de la corbeille ?')) {{
+                    fetch('/trash/empty', {{
+                        method: 'POST'
+                    }}).then(() => location.reload());
+                }}
+            }}
+            
+            // Rafraîchir rapidement tant que des fichiers sont en cours d'analyse
+            if ({} > 0) {{
+                setTimeout(() => location.reload(), 3000);
+            }}
+            
             // Auto-refresh avec countdown
             let countdown = {};
             const statusEl = document.getElementById('status');
+            const healthEl = document.getElementById('health');
+            const refreshEl = document.getElementById('refresh');
             
             const timer = setInterval(() => {{
                 countdown--;
                 if (countdown > 0) {{
-                    statusEl.textContent = `🔄 Refresh dans ${{countdown}}s`;
+                    refreshEl.textContent = `🔄 ${{countdown}}s`;
                 }} else {{
                     location.reload();
                 }}
             }}, 1000);
+            
+            // Le lien d'un partage chiffré n'est affiché qu'une fois : ne pas le perdre
+            function pauseRefresh() {{
+                clearInterval(timer);
+                refreshEl.textContent = '⏸️ Actualisation suspendue';
+            }}
+            
+            // État réel du serveur (sonde /readyz), le détail des échecs en infobulle
+            function checkHealth() {{
+                fetch('/readyz', {{ cache: 'no-store' }})
+                    .then(response => response.json().then(data => ({{ ready: response.ok, data }})))
+                    .then(({{ ready, data }}) => {{
+                        const failed = (data.checks || []).filter(check => !check.ok);
+                        statusEl.className = ready ? 'status-bar' : 'status-bar degraded';
+                        statusEl.title = failed.map(check => `${{check.name}}: ${{check.detail}}`).join('\n');
+                        healthEl.textContent = ready ? '🟢 Prêt' : '🟠 Uploads indisponibles';
+                    }})
+                    .catch(() => {{
+                        statusEl.className = 'status-bar down';
+                        statusEl.title = '';
+                        healthEl.textContent = '🔴 Serveur injoignable';
+                    }});
+            }}
+            checkHealth();
+            setInterval(checkHealth, 10000);
+            
+            renderSecureShares();
         </script>
     </body>
     </html>
-    "#, refresh_interval / 1000));
+    "#, pending.len(), refresh_interval / 1000));
 
     html
 }
 
+fn format_file_details(file: &FileInfo) -> String {
+    let mut details = String::new();
+    if let Some(description) = &file.description {
+        details.push_str(&format!(r#"<div class="file-description">{}</div>"#, escape_html(description)));
+    }
+    if !file.tags.is_empty() {
+        let tags: String = file
+            .tags
+            .iter()
+            .map(|tag| format!(r#"<span class="tag">🏷️ {}</span>"#, escape_html(tag)))
+            .collect();
+        details.push_str(&format!(r#"<div class="file-tags">{}</div>"#, tags));
+    }
+    if let Some(sha256) = &file.sha256 {
+        let mut title = format!("SHA-256: {}", sha256);
+        if let Some(blake3) = &file.blake3 {
+            title.push_str(&format!("&#10;BLAKE3: {}", blake3));
+        }
+        let state = match file.verified_at {
+            _ if file.corrupt => " ⚠️ corrompu".to_string(),
+            Some(at) => format!(" ✅ vérifié ({})", format_timestamp(at).to_lowercase()),
+            None => String::new(),
+        };
+        details.push_str(&format!(
+            r#"<div class="file-hash{}" title="{}">🧾 {}…{}</div>"#,
+            if file.corrupt { " corrupt" } else { "" },
+            title,
+            sha256.chars().take(16).collect::<String>(),
+            state
+        ));
+    }
+    details
+}
+
+fn format_version_history(id: &str, history: Option<&Vec<VersionEntry>>) -> String {
+    let Some(history) = history else {
+        return String::new();
+    };
+
+    let items: String = history
+        .iter()
+        .map(|version| format!(r#"
+                            <li>
+                                📏 {} | 🕒 {}
+                                <a href="/versions/{}/{}" title="Télécharger cette version">⬇️</a>
+                                <a href="javascript:void(0)" onclick="restoreVersion('{}', '{}')" title="Restaurer cette version">♻️</a>
+                            </li>
+        "#, format_file_size(version.size), format_timestamp(version.uploaded_at), id, version.id, id, version.id))
+        .collect();
+
+    format!(r#"
+                    <details class="versions">
+                        <summary>🕘 {} version(s) précédente(s)</summary>
+                        <ul>{}</ul>
+                    </details>
+    "#, history.len(), items)
+}
+
+fn format_usage_meter(usage: &StorageUsage) -> String {
+    // Sans quota, la jauge représente la part du disque occupée par le partage
+    let capacity = usage.quota.unwrap_or(usage.used + usage.available);
+    let percent = if capacity > 0 {
+        (usage.used as f64 / capacity as f64 * 100.0).min(100.0)
+    } else {
+        0.0
+    };
+    let limit = match usage.quota {
+        Some(quota) => format!(" / {}", format_file_size(quota)),
+        None => String::new(),
+    };
+
+    format!(r#"
+                <div class="usage-meter">
+                    💾 {}{} utilisés · {} libres sur le disque
+                    <div class="usage-bar"><div class="usage-fill{}" style="width: {:.1}%;"></div></div>
+                </div>
+    "#, format_file_size(usage.used), limit, format_file_size(usage.available), if percent >= 90.0 { " high" } else { "" }, percent)
+}
+
+fn format_policy_info(policy: &UploadPolicy) -> String {
+    let mut lines = Vec::new();
+
+    let allowed = policy.allowed_labels();
+    if !allowed.is_empty() {
+        lines.push(format!("✅ Types autorisés: {}", allowed.join(", ")));
+    }
+    let blocked = policy.blocked_labels();
+    if !blocked.is_empty() {
+        lines.push(format!("⛔ Types interdits: {}", blocked.join(", ")));
+    }
+    let limits = policy.size_limit_labels();
+    if !limits.is_empty() {
+        let limits = limits
+            .iter()
+            .map(|(label, size)| format!("{} ≤ {} MB", label, size / (1024 * 1024)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        lines.push(format!("📏 Limites par type: {}", limits));
+    }
+
+    lines
+        .iter()
+        .map(|line| format!(r#"<p class="policy-info">{}</p>"#, line))
+        .collect()
+}
+
 fn get_uploaded_files(upload_dir: &str) -> Vec<FileInfo> {
     let mut files = Vec::new();
+    // Une seule lecture de l'index plutôt qu'une par fichier
+    let mut known = store::all(upload_dir).unwrap_or_else(|e| {
+        warn!(error = %e, "⚠️ Erreur lecture index métadonnées");
+        HashMap::new()
+    });
     
-    if let Ok(entries) = fs::read_dir(upload_dir) {
-        for entry in entries.flatten() {
-            if let Ok(metadata) = entry.metadata() {
-                if metadata.is_file() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    let size = metadata.len();
-                    let uploaded_at = metadata
-                        .modified()
-                        .unwrap_or(SystemTime::UNIX_EPOCH)
-                        .duration_since(UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs();
-                    
-                    let file_type = Path::new(&name)
-                        .extension()
-                        .map_or("unknown".to_string(), |ext| ext.to_string_lossy().to_lowercase());
-                    
-                    files.push(FileInfo {
-                        name,
-                        size,
-                        uploaded_at,
-                        file_type,
-                    });
-                }
-            }
-        }
+    let objects = storage::open(upload_dir).list("").unwrap_or_else(|e| {
+        warn!(error = %e, "⚠️ Erreur lecture du stockage");
+        Vec::new()
+    });
+    // Les fichiers cachés appartiennent au serveur (staging, analyse...)
+    for object in objects.into_iter().filter(|o| names::valid_id(&o.key)) {
+        let file_meta = known.remove(&object.key).unwrap_or_else(|| meta::load(upload_dir, &object.key));
+        files.push(file_info(object, file_meta));
     }
     
-    // Trier par date de modification (plus récent en premier)
-    files.sort_by(|a, b| b.uploaded_at.cmp(&a.uploaded_at));
+    // Trier par date d'upload (plus récent en premier)
+    files.sort_by_key(|f| std::cmp::Reverse(f.uploaded_at));
     files
 }
 
-fn sanitize_filename(filename: &str) -> String {
-    filename
-        .chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '.' || c == '-' || c == '_' || c == ' ' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>()
-        .trim()
-        .to_string()
+// Un seul fichier partagé, None s'il n'existe pas (ou plus)
+fn get_uploaded_file(upload_dir: &str, id: &str) -> Option<FileInfo> {
+    if !names::valid_id(id) {
+        return None;
+    }
+    let object = storage::open(upload_dir).stat(id).ok().flatten()?;
+    Some(file_info(object, meta::load(upload_dir, id)))
+}
+
+fn file_info(object: ObjectInfo, file_meta: FileMeta) -> FileInfo {
+    // Fichiers antérieurs à l'index : la date de modification fait office de date d'upload
+    let uploaded_at = file_meta.uploaded_at.unwrap_or(object.modified);
+    let name = file_meta.name.unwrap_or_else(|| object.key.clone());
+    let file_type = names::file_type(&name);
+
+    FileInfo {
+        id: object.key,
+        name,
+        size: object.size,
+        uploaded_at,
+        file_type,
+        expires_at: file_meta.expires_at,
+        uploader: file_meta.uploader,
+        sha256: file_meta.sha256,
+        blake3: file_meta.blake3,
+        verified_at: file_meta.verified_at,
+        corrupt: file_meta.corrupt,
+        mime: file_meta.mime,
+        description: file_meta.description,
+        tags: file_meta.tags,
+    }
+}
+
+// Les noms d'origine peuvent contenir n'importe quel caractère
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
 }
 
 fn get_file_icon(file_type: &str) -> &'static str {
@@ -763,6 +1933,18 @@ fn format_timestamp(timestamp: u64) -> String {
     }
 }
 
+fn format_remaining(expires_at: u64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    if expires_at <= now {
+        "Expiré".to_string()
+    } else {
+        format!("Expire dans {}", retention::format_duration(expires_at - now))
+    }
+}
+
 fn get_local_ips() -> Vec<String> {
     let mut ips = Vec::new();
     
@@ -778,7 +1960,7 @@ fn get_local_ips() -> Vec<String> {
             }
         }
         Err(e) => {
-            eprintln!("⚠️ Erreur lecture interfaces réseau: {}", e);
+            warn!(error = %e, "⚠️ Erreur lecture interfaces réseau");
         }
     }
     
@@ -789,7 +1971,7 @@ fn generate_qr_code_svg(url: &str) -> String {
     match qrcode_generator::to_svg_to_string(url, QrCodeEcc::Medium, 200, None::<&str>) {
         Ok(svg) => svg,
         Err(e) => {
-            eprintln!("❌ Erreur génération QR code pour '{}': {}", url, e);
+            error!(url, error = %e, "❌ Erreur génération QR code");
             r#"<div style="color: #ff6b6b;">❌ QR code indisponible</div>"#.to_string()
         }
     }
@@ -797,14 +1979,58 @@ fn generate_qr_code_svg(url: &str) -> String {
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
+    health::start();
     let config = Config::from_env();
+    config.logging.init();
     let local_ips = get_local_ips();
     
     // Créer le dossier d'upload
     fs::create_dir_all(&config.upload_dir).unwrap_or_else(|e| {
-        eprintln!("⚠️ Erreur création dossier upload: {}", e);
+        warn!(error = %e, "⚠️ Erreur création dossier upload");
     });
+
+    // Le stockage doit être joignable avant d'accepter des fichiers
+    if let Err(e) = config.storage.open(&config.upload_dir).list("") {
+        error!(storage = %config.storage.describe(), error = %e, "❌ Stockage inaccessible");
+        return Err(e);
+    }
+
+    // Charger la clé de chiffrement (phrase de passe ou fichier de clé)
+    let encryption = EncryptionConfig::from_env();
+    match crypto::init(&config.upload_dir, &encryption, &config.storage) {
+        Ok(true) => match crypto::encrypt_existing(&config.upload_dir, &config.storage, config.dedup.enabled) {
+            Ok(0) => {}
+            Ok(count) => info!(count, "🔐 Fichiers existants chiffrés"),
+            Err(e) => warn!(error = %e, "⚠️ Erreur chiffrement des fichiers existants"),
+        },
+        Ok(false) => {}
+        Err(e) => {
+            error!(error = %e, "❌ Chiffrement impossible");
+            return Err(e);
+        }
+    }
+
+    // Les fichiers restés en staging proviennent d'uploads interrompus
+    let _ = fs::remove_dir_all(PathBuf::from(&config.upload_dir).join(STAGING_DIR));
+
+    // Remettre l'index des métadonnées en accord avec le dossier (ou le reconstruire)
+    match store::sync(&config.upload_dir) {
+        Ok(report) if report.added > 0 || report.removed > 0 => info!(
+            indexed = report.indexed,
+            added = report.added,
+            removed = report.removed,
+            "🗂️ Index des métadonnées synchronisé"
+        ),
+        Ok(_) => {}
+        Err(e) => warn!(error = %e, "⚠️ Erreur synchronisation index métadonnées"),
+    }
+    if config.dedup.enabled {
+        match dedup::adopt_existing(&config.upload_dir) {
+            Ok(0) => {}
+            Ok(saved) => info!(saved, "🧬 Déduplication des fichiers existants"),
+            Err(e) => warn!(error = %e, "⚠️ Erreur déduplication des fichiers existants"),
+        }
+    }
     
     println!("╔════════════════════════════════════════════════════════════════════╗");
     println!("║                    📁 SERVEUR DE PARTAGE DE FICHIERS                ║");
@@ -820,24 +2046,86 @@ async fn main() -> std::io::Result<()> {
     println!("║ ⚙️  Configuration:                                                ║");
     println!("║   🔌 Port: {}                                                  ║", config.port);
     println!("║   📁 Dossier upload: {}                                       ║", config.upload_dir);
+    println!("║   💽 Stockage: {}                                         ║", config.storage.describe());
     println!("║   📏 Taille max: {} MB                                         ║", config.max_file_size / (1024 * 1024));
+    println!("║   💾 Quota: {}                                              ║", config.quota.describe());
+    println!("║   ⌛ Rétention: {}                                              ║", config.retention.describe());
+    println!("║   🗑️  Corbeille: purge après {}                                 ║", config.trash.describe());
+    println!("║   🔀 Conflits de noms: {}                                    ║", config.conflict_policy.label());
+    println!("║   🧬 Déduplication: {}                                         ║", config.dedup.describe());
+    println!("║   🔐 Chiffrement: {}                                           ║", encryption.describe());
+    println!("║   🔒 Partages chiffrés: {}                                     ║", config.secure.describe());
+    println!("║   🧾 Sommes de contrôle: {}                                    ║", config.checksum.describe());
+    println!("║   🚦 Limites de débit: {}                                     ║", config.throttle.describe());
+    println!("║   📜 Journal d'audit: {}                                       ║", config.audit.describe());
+    println!("║   🪵 Logs: {}                                                  ║", config.logging.describe());
+    println!("║   🪝 Webhooks: {}                       ║", config.webhook.describe());
+    println!("║   📊 Métriques: {}                                             ║", config.metrics.describe());
+    println!("║   🩺 Sondes: {}                                ║", config.health.describe());
+    println!("║   🛑 Arrêt: {}                                 ║", config.shutdown.describe());
+    println!("║   🧱 Accès: {}                                                  ║", config.access.describe());
+    println!("║   🚥 Limites de requêtes: {}                                  ║", config.rate_limit.describe());
+    println!("║   🗜️  Compression: {}                              ║", config.compression.describe());
     println!("║   🔄 Auto-refresh: {}s                                         ║", config.refresh_interval / 1000);
+    println!("║   🛡️  Analyse antivirus: {}                                    ║", config.scan.describe());
     println!("║   🖥️  Interface: 0.0.0.0 (toutes)                             ║");
     println!("╚════════════════════════════════════════════════════════════════════╝");
     
-    HttpServer::new(move || {
+    let compression = config.compression.enabled;
+    let server = HttpServer::new(move || {
         App::new()
-            .wrap(Logger::default())
             .wrap(DefaultHeaders::new()
                 .add(("X-Content-Type-Options", "nosniff"))
                 .add(("X-Frame-Options", "SAMEORIGIN"))
             )
+            .wrap(from_fn(ratelimit::middleware))
+            .wrap(from_fn(access::middleware))
+            // Autour des règles d'accès pour que leurs refus portent aussi un identifiant,
+            // sous la compression pour lire les corps d'erreur en clair
+            .wrap(from_fn(logging::middleware))
+            .wrap(from_fn(metrics::middleware))
+            .wrap(Condition::new(compression, Compress::default()))
             .service(index)
             .service(upload_file)
             .service(download_file)
             .service(delete_file)
+            .service(trash::restore)
+            .service(trash::purge)
+            .service(trash::empty)
+            .service(versions::history)
+            .service(versions::download_version)
+            .service(versions::restore)
+            .service(meta::list)
+            .service(meta::details)
+            .service(meta::update_details)
+            .service(integrity::verify_file)
+            .service(audit::view)
+            .service(metrics::metrics)
+            .service(health::healthz)
+            .service(health::readyz)
+            .service(health::info)
+            .service(secure::script)
+            .service(secure::upload)
+            .service(secure::info)
+            .service(secure::blob)
+            .service(secure::delete)
+            .service(secure::page)
     })
     .bind(("0.0.0.0", config.port))?
-    .run()
-    .await
+    // Les signaux sont gérés par shutdown::watch pour le bilan et le nettoyage
+    .disable_signals()
+    .shutdown_timeout(config.shutdown.timeout)
+    .run();
+    shutdown::watch(server.handle(), config.upload_dir.clone());
+
+    // Avant la reprise des analyses, qui peuvent publier des fichiers
+    webhook::spawn_worker(&config.webhook);
+    // Reprendre les analyses interrompues par un arrêt du serveur
+    scan::resume_pending(&config.upload_dir, &config.scan);
+    retention::spawn_reaper(config.upload_dir.clone(), config.retention.clone(), config.trash.clone());
+    integrity::spawn_scrubber(config.upload_dir.clone(), &config.checksum);
+
+    server.await?;
+    shutdown::finish(&config.upload_dir);
+    Ok(())
 }
\ No newline at end of file
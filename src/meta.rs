@@ -0,0 +1,243 @@
+// Métadonnées associées aux fichiers partagés (par identifiant de stockage).
+// Chaque fichier a son .meta/<id>.json, l'index SQLite (store.rs) sert aux lectures.
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use actix_web::{get, post, web, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{error, warn};
+
+use crate::{cache, crypto, get_uploaded_files, names, storage, store, Config};
+
+const META_DIR: &str = ".meta";
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LEN: usize = 40;
+pub const MAX_DESCRIPTION_LEN: usize = 1000;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileMeta {
+    // Nom d'origine du fichier (NFC), None pour les fichiers déposés sous leur nom
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    // Date d'expiration (timestamp unix), None = conservé indéfiniment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    // Date de l'upload (la date de modification du fichier peut changer)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploaded_at: Option<u64>,
+    // Adresse IP du client qui a envoyé le fichier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploader: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    // Empreinte BLAKE3, calculée seulement si CHECKSUM_BLAKE3 est actif
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blake3: Option<String>,
+    // Dernière vérification d'intégrité et son résultat
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub corrupt: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl FileMeta {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none()
+            && self.expires_at.is_none()
+            && self.uploaded_at.is_none()
+            && self.uploader.is_none()
+            && self.sha256.is_none()
+            && self.blake3.is_none()
+            && self.verified_at.is_none()
+            && !self.corrupt
+            && self.mime.is_none()
+            && self.description.is_none()
+            && self.tags.is_empty()
+    }
+}
+
+pub fn meta_dir(upload_dir: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(META_DIR)
+}
+
+fn meta_path(upload_dir: &str, id: &str) -> PathBuf {
+    meta_dir(upload_dir).join(format!("{}.json", id))
+}
+
+pub fn load(upload_dir: &str, id: &str) -> FileMeta {
+    match store::get(upload_dir, id) {
+        Ok(Some(meta)) => meta,
+        Ok(None) => read_sidecar(upload_dir, id),
+        Err(e) => {
+            warn!(id, error = %e, "⚠️ Erreur lecture index métadonnées");
+            read_sidecar(upload_dir, id)
+        }
+    }
+}
+
+pub fn save(upload_dir: &str, id: &str, meta: &FileMeta) -> std::io::Result<()> {
+    if meta.is_empty() {
+        remove(upload_dir, id);
+        return Ok(());
+    }
+    write_sidecar(upload_dir, id, meta)?;
+    // Le fichier .json fait foi : un index en retard est rattrapé au prochain démarrage
+    if let Err(e) = store::upsert(upload_dir, id, meta) {
+        warn!(id, error = %e, "⚠️ Erreur mise à jour index métadonnées");
+    }
+    cache::invalidate_index();
+    Ok(())
+}
+
+pub fn remove(upload_dir: &str, id: &str) {
+    if let Err(e) = remove_file_if_exists(&meta_path(upload_dir, id)) {
+        warn!(id, error = %e, "⚠️ Erreur suppression métadonnées");
+    }
+    if let Err(e) = store::delete(upload_dir, id) {
+        warn!(id, error = %e, "⚠️ Erreur mise à jour index métadonnées");
+    }
+    cache::invalidate_index();
+}
+
+pub fn read_sidecar(upload_dir: &str, id: &str) -> FileMeta {
+    fs::read_to_string(meta_path(upload_dir, id))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn write_sidecar(upload_dir: &str, id: &str, meta: &FileMeta) -> std::io::Result<()> {
+    fs::create_dir_all(meta_dir(upload_dir))?;
+    let content = serde_json::to_string(meta).map_err(std::io::Error::other)?;
+    fs::write(meta_path(upload_dir, id), content)
+}
+
+fn remove_file_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+// Empreinte du contenu en clair d'un fichier local (éventuellement chiffré)
+pub fn file_sha256(path: &Path) -> std::io::Result<String> {
+    let mut file = crypto::open_file(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+// "photos, vacances 2024" -> ["photos", "vacances 2024"]
+pub fn parse_tags(raw: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+        let tag: String = tag.chars().filter(|c| !c.is_control()).take(MAX_TAG_LEN).collect();
+        if !tags.contains(&tag) && tags.len() < MAX_TAGS {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+pub fn parse_description(raw: &str) -> Option<String> {
+    let description: String = raw
+        .trim()
+        .chars()
+        .filter(|c| !c.is_control() || *c == '\n')
+        .take(MAX_DESCRIPTION_LEN)
+        .collect();
+    Some(description).filter(|d| !d.is_empty())
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    tag: Option<String>,
+}
+
+#[get("/files")]
+async fn list(query: web::Query<ListQuery>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let files: Vec<_> = get_uploaded_files(&config.upload_dir)
+        .into_iter()
+        .filter(|f| query.tag.as_ref().is_none_or(|tag| f.tags.contains(tag)))
+        .collect();
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "files": files })))
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Fichier non trouvé"
+    }))
+}
+
+#[get("/files/{id}")]
+async fn details(path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    match get_uploaded_files(&config.upload_dir).into_iter().find(|f| f.id == id) {
+        Some(file) => Ok(HttpResponse::Ok().json(file)),
+        None => Ok(not_found()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DetailsForm {
+    description: Option<String>,
+    tags: Option<String>,
+}
+
+// Modifie la description et les tags d'un fichier partagé
+#[post("/files/{id}")]
+async fn update_details(path: web::Path<String>, form: web::Form<DetailsForm>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    if !names::valid_id(&id) {
+        return Ok(not_found());
+    }
+    let upload_dir = config.upload_dir.clone();
+    let object_id = id.clone();
+    if web::block(move || storage::open(&upload_dir).stat(&object_id)).await??.is_none() {
+        return Ok(not_found());
+    }
+
+    let form = form.into_inner();
+    let upload_dir = config.upload_dir.clone();
+    let updated = web::block(move || {
+        let mut file_meta = load(&upload_dir, &id);
+        if let Some(description) = form.description {
+            file_meta.description = parse_description(&description);
+        }
+        if let Some(tags) = form.tags {
+            file_meta.tags = parse_tags(&tags);
+        }
+        save(&upload_dir, &id, &file_meta).map(|_| file_meta)
+    })
+    .await?;
+
+    match updated {
+        Ok(file_meta) => Ok(HttpResponse::Ok().json(file_meta)),
+        Err(e) => {
+            error!(error = %e, "❌ Erreur mise à jour métadonnées");
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de l'enregistrement"
+            })))
+        }
+    }
+}
@@ -0,0 +1,340 @@
+// Métriques au format texte de Prometheus : transferts, requêtes HTTP par
+// route, stockage et nombre de fichiers, exposées sur /metrics
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write as _,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    get,
+    http::header,
+    middleware::Next,
+    web, Error, HttpRequest, HttpResponse, Result,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{get_uploaded_files, ratelimit, scan, secure, throttle::Direction, trash, Config};
+
+const PREFIX: &str = "sharing";
+// Bornes des histogrammes, en secondes
+const HTTP_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+const TRANSFER_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0];
+// Route des requêtes qui ne correspondent à aucun handler (évite une série par URL)
+const UNMATCHED: &str = "inconnue";
+
+#[derive(Debug, Clone)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    // Jeton exigé pour lire /metrics (None = accès libre)
+    token: Option<String>,
+}
+
+impl MetricsConfig {
+    pub fn from_env() -> Self {
+        Self {
+            enabled: env::var("METRICS")
+                .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            token: env::var("METRICS_TOKEN").ok().filter(|t| !t.trim().is_empty()),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.enabled, &self.token) {
+            (false, _) => "désactivées".to_string(),
+            (true, Some(_)) => "/metrics (jeton requis)".to_string(),
+            (true, None) => "/metrics".to_string(),
+        }
+    }
+
+    // Jeton attendu en "Authorization: Bearer <jeton>" ou en paramètre ?token=
+    fn authorized(&self, req: &HttpRequest) -> bool {
+        let Some(expected) = &self.token else {
+            return true;
+        };
+        let bearer = req
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        let query = web::Query::<BTreeMap<String, String>>::from_query(req.query_string())
+            .ok()
+            .and_then(|q| q.get("token").cloned());
+        // Comparaison des empreintes : durée indépendante du contenu du jeton
+        let hash = |token: &str| Sha256::digest(token.trim().as_bytes());
+        bearer
+            .map(str::to_string)
+            .or(query)
+            .is_some_and(|token| hash(&token) == hash(expected))
+    }
+}
+
+struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Self {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if seconds <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let with = |extra: String| {
+            let all: Vec<&str> = [labels, extra.as_str()].into_iter().filter(|l| !l.is_empty()).collect();
+            if all.is_empty() { String::new() } else { format!("{{{}}}", all.join(",")) }
+        };
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{}_bucket{} {}", name, with(format!("le=\"{}\"", bound)), count);
+        }
+        let _ = writeln!(out, "{}_bucket{} {}", name, with("le=\"+Inf\"".to_string()), self.count);
+        let _ = writeln!(out, "{}_sum{} {}", name, with(String::new()), self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, with(String::new()), self.count);
+    }
+}
+
+struct Transfers {
+    count: u64,
+    bytes: u64,
+    duration: Histogram,
+    failures: BTreeMap<String, u64>,
+    active: u64,
+}
+
+impl Default for Transfers {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            bytes: 0,
+            duration: Histogram::new(TRANSFER_BUCKETS),
+            failures: BTreeMap::new(),
+            active: 0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    uploads: Transfers,
+    downloads: Transfers,
+    // (méthode, route, statut) -> nombre de réponses
+    requests: BTreeMap<(String, String, u16), u64>,
+    durations: BTreeMap<String, Histogram>,
+}
+
+impl Registry {
+    fn transfers(&mut self, direction: Direction) -> &mut Transfers {
+        match direction {
+            Direction::Upload => &mut self.uploads,
+            Direction::Download => &mut self.downloads,
+        }
+    }
+}
+
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Mutex::default);
+
+// Transfert mené à son terme
+pub fn transfer_completed(direction: Direction, bytes: u64, duration: Duration) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let transfers = registry.transfers(direction);
+    transfers.count += 1;
+    transfers.bytes += bytes;
+    transfers.duration.observe(duration);
+}
+
+// Transfert refusé ou interrompu ; les octets déjà passés sont comptés
+pub fn transfer_failed(direction: Direction, reason: &str, bytes: u64) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let transfers = registry.transfers(direction);
+    transfers.bytes += bytes;
+    *transfers.failures.entry(reason.to_string()).or_default() += 1;
+}
+
+pub fn transfer_started(direction: Direction) {
+    REGISTRY.lock().unwrap().transfers(direction).active += 1;
+}
+
+pub fn transfer_ended(direction: Direction) {
+    let mut registry = REGISTRY.lock().unwrap();
+    let transfers = registry.transfers(direction);
+    transfers.active = transfers.active.saturating_sub(1);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TransferCounts {
+    pub active: u64,
+    pub completed: u64,
+}
+
+// Transferts en cours et menés à terme depuis le démarrage
+pub fn transfer_counts(direction: Direction) -> TransferCounts {
+    let mut registry = REGISTRY.lock().unwrap();
+    let transfers = registry.transfers(direction);
+    TransferCounts {
+        active: transfers.active,
+        completed: transfers.count,
+    }
+}
+
+// Compte chaque réponse par route (motif du handler, pas l'URL) et statut
+pub async fn middleware(req: ServiceRequest, next: Next<impl MessageBody>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let started = Instant::now();
+    let response = next.call(req).await?;
+    let route = response
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| UNMATCHED.to_string());
+    let method = response.request().method().to_string();
+    let status = response.status().as_u16();
+    let mut registry = REGISTRY.lock().unwrap();
+    *registry.requests.entry((method, route.clone(), status)).or_default() += 1;
+    registry
+        .durations
+        .entry(route)
+        .or_insert_with(|| Histogram::new(HTTP_BUCKETS))
+        .observe(started.elapsed());
+    drop(registry);
+    Ok(response)
+}
+
+fn escape_label(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn render_transfers(out: &mut String, registry: &Registry) {
+    let directions = [("upload", &registry.uploads), ("download", &registry.downloads)];
+    for (label, transfers) in directions {
+        let name = format!("{}_{}s_total", PREFIX, label);
+        header(out, &name, "counter", &format!("Transferts ({}) menés à terme", label));
+        let _ = writeln!(out, "{} {}", name, transfers.count);
+
+        let name = format!("{}_{}_bytes_total", PREFIX, label);
+        header(out, &name, "counter", &format!("Octets transférés ({})", label));
+        let _ = writeln!(out, "{} {}", name, transfers.bytes);
+
+        let name = format!("{}_{}_duration_seconds", PREFIX, label);
+        header(out, &name, "histogram", &format!("Durée des transferts ({}) menés à terme", label));
+        transfers.duration.render(out, &name, "");
+
+        let name = format!("{}_{}_failures_total", PREFIX, label);
+        header(out, &name, "counter", &format!("Transferts ({}) refusés ou interrompus, par raison", label));
+        for (reason, count) in &transfers.failures {
+            let _ = writeln!(out, "{}{{reason=\"{}\"}} {}", name, escape_label(reason), count);
+        }
+    }
+
+    let name = format!("{}_active_transfers", PREFIX);
+    header(out, &name, "gauge", "Transferts en cours");
+    for (label, transfers) in directions {
+        let _ = writeln!(out, "{}{{direction=\"{}\"}} {}", name, label, transfers.active);
+    }
+}
+
+fn render_http(out: &mut String, registry: &Registry) {
+    let name = format!("{}_http_requests_total", PREFIX);
+    header(out, &name, "counter", "Réponses HTTP par méthode, route et statut");
+    for ((method, route, status), count) in &registry.requests {
+        let _ = writeln!(
+            out,
+            "{}{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+            name,
+            escape_label(method),
+            escape_label(route),
+            status,
+            count
+        );
+    }
+
+    let name = format!("{}_http_request_duration_seconds", PREFIX);
+    header(out, &name, "histogram", "Durée de traitement des requêtes HTTP par route");
+    for (route, histogram) in &registry.durations {
+        histogram.render(out, &name, &format!("route=\"{}\"", escape_label(route)));
+    }
+}
+
+// Jauges calculées à la lecture : stockage et fichiers
+fn render_storage(out: &mut String, config: &Config) {
+    let usage = config.quota.usage(&config.upload_dir);
+    let name = format!("{}_storage_used_bytes", PREFIX);
+    header(out, &name, "gauge", "Espace occupé par les fichiers partagés");
+    let _ = writeln!(out, "{} {}", name, usage.used);
+    let name = format!("{}_storage_free_bytes", PREFIX);
+    header(out, &name, "gauge", "Espace libre sur le disque du dossier d'upload");
+    let _ = writeln!(out, "{} {}", name, usage.available);
+    if let Some(quota) = usage.quota {
+        let name = format!("{}_storage_quota_bytes", PREFIX);
+        header(out, &name, "gauge", "Quota de stockage");
+        let _ = writeln!(out, "{} {}", name, quota);
+    }
+
+    let name = format!("{}_files", PREFIX);
+    header(out, &name, "gauge", "Nombre de fichiers par état");
+    let counts = [
+        ("shared", get_uploaded_files(&config.upload_dir).len()),
+        ("pending", scan::pending_files(&config.upload_dir).len()),
+        ("quarantined", scan::quarantined_count(&config.upload_dir)),
+        ("trash", trash::list(&config.upload_dir).len()),
+        ("secure", secure::active_count(&config.upload_dir)),
+    ];
+    for (state, count) in counts {
+        let _ = writeln!(out, "{}{{state=\"{}\"}} {}", name, state, count);
+    }
+}
+
+#[get("/metrics")]
+async fn metrics(req: HttpRequest) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    if !config.metrics.enabled {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+    if !config.metrics.authorized(&req) {
+        ratelimit::auth_failure(&req);
+        return Ok(HttpResponse::Unauthorized()
+            .insert_header((header::WWW_AUTHENTICATE, "Bearer"))
+            .json(serde_json::json!({ "error": "Jeton de métriques invalide" })));
+    }
+
+    let mut out = web::block(move || {
+        let mut out = String::new();
+        render_storage(&mut out, &config);
+        out
+    })
+    .await?;
+    {
+        let registry = REGISTRY.lock().unwrap();
+        render_transfers(&mut out, &registry);
+        render_http(&mut out, &registry);
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(out))
+}
@@ -0,0 +1,133 @@
+// Noms des fichiers partagés : le nom d'origine (Unicode) est conservé dans les
+// métadonnées, le fichier est stocké sur le disque sous un identifiant sûr
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::http::header::{
+    Charset, ContentDisposition, DispositionParam, DispositionType, ExtendedValue,
+};
+use tracing::warn;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{meta, storage, store};
+
+// Limite des systèmes de fichiers courants, gardée pour les téléchargements
+const MAX_NAME_LEN: usize = 255;
+
+// Nom d'affichage d'un fichier envoyé : normalisé NFC (un "é" composé ou décomposé
+// donne le même nom), sans chemin ni caractères de contrôle
+pub fn normalize(raw: &str) -> String {
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or(raw);
+    let mut name: String = base
+        .nfc()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .to_string();
+    if name.len() > MAX_NAME_LEN {
+        let mut end = MAX_NAME_LEN;
+        while !name.is_char_boundary(end) {
+            end -= 1;
+        }
+        name.truncate(end);
+    }
+    if name.is_empty() || name == "." || name == ".." {
+        return "fichier".to_string();
+    }
+    name
+}
+
+// Nom envoyé par le client : `filename*` (RFC 5987) s'il est présent, sinon `filename`
+pub fn upload_name(content_disposition: &ContentDisposition) -> Option<String> {
+    let ext = content_disposition
+        .get_filename_ext()
+        .filter(|ext| ext.charset.to_string().eq_ignore_ascii_case("utf-8"))
+        .and_then(|ext| String::from_utf8(ext.value.clone()).ok());
+    ext.or_else(|| content_disposition.get_filename().map(str::to_string))
+        .map(|name| normalize(&name))
+}
+
+// Identifiant de stockage d'un nouveau fichier (timestamp en nanosecondes)
+pub fn new_id(upload_dir: &str) -> String {
+    loop {
+        let id = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+            .to_string();
+        if !PathBuf::from(upload_dir).join(&id).exists() {
+            return id;
+        }
+    }
+}
+
+// Les fichiers déposés avant le stockage par identifiant gardent leur nom comme
+// identifiant : seuls les noms pouvant sortir du dossier ou casser une URL sont refusés
+pub fn valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && !id.starts_with('.')
+        && !id.chars().any(|c| c.is_control() || matches!(c, '/' | '\\' | '\'' | '"' | '<' | '>' | '&'))
+}
+
+// Nom d'affichage d'un fichier stocké, son identifiant à défaut
+pub fn display_name(upload_dir: &str, id: &str) -> String {
+    meta::load(upload_dir, id).name.unwrap_or_else(|| id.to_string())
+}
+
+// Noms d'affichage des fichiers partagés et identifiants correspondants
+pub fn index(upload_dir: &str) -> HashMap<String, String> {
+    let mut known = store::all(upload_dir).unwrap_or_default();
+    storage::open(upload_dir)
+        .list("")
+        .map(|objects| {
+            objects
+                .into_iter()
+                .map(|object| object.key)
+                .filter(|id| valid_id(id))
+                .map(|id| match known.remove(&id) {
+                    Some(file_meta) => (file_meta.name.unwrap_or_else(|| id.clone()), id),
+                    None => (display_name(upload_dir, &id), id),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// Résolution d'un seul nom par l'index SQLite, sans lister le stockage ;
+// le listage ne sert que si l'index est illisible
+pub fn find(upload_dir: &str, name: &str) -> Option<String> {
+    store::find_by_name(upload_dir, name).unwrap_or_else(|e| {
+        warn!(error = %e, "⚠️ Erreur lecture index métadonnées");
+        index(upload_dir).remove(name)
+    })
+}
+
+pub fn file_type(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .map_or("unknown".to_string(), |ext| ext.to_string_lossy().to_lowercase())
+}
+
+// En-tête de téléchargement : `filename` en ASCII pour les anciens clients,
+// `filename*` en UTF-8 (RFC 6266) pour le nom exact
+pub fn content_disposition(name: &str) -> ContentDisposition {
+    let fallback: String = name
+        .chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' { c } else { '_' })
+        .collect();
+    let mut parameters = vec![DispositionParam::Filename(fallback)];
+    if !name.is_ascii() {
+        parameters.push(DispositionParam::FilenameExt(ExtendedValue {
+            charset: Charset::Ext("UTF-8".to_string()),
+            language_tag: None,
+            value: name.as_bytes().to_vec(),
+        }));
+    }
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters,
+    }
+}
@@ -0,0 +1,209 @@
+// Politique de contenu des uploads : types autorisés / bloqués et limites de taille par type
+use std::{env, path::Path};
+
+// Nombre d'octets nécessaires pour reconnaître un type par ses "magic bytes"
+pub const SNIFF_LEN: usize = 8192;
+
+#[derive(Debug, Clone, PartialEq)]
+enum TypeRule {
+    // Extension de fichier, ex: "exe"
+    Extension(String),
+    // Type MIME exact ou générique, ex: "application/pdf" ou "image/*"
+    Mime(String),
+}
+
+impl TypeRule {
+    fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim().to_lowercase();
+        if raw.is_empty() {
+            None
+        } else if raw.contains('/') {
+            Some(TypeRule::Mime(raw))
+        } else {
+            Some(TypeRule::Extension(raw.trim_start_matches('.').to_string()))
+        }
+    }
+
+    fn matches(&self, kind: &FileKind) -> bool {
+        match self {
+            TypeRule::Extension(ext) => kind.extension.as_deref() == Some(ext.as_str()),
+            TypeRule::Mime(pattern) => match pattern.strip_suffix("/*") {
+                Some(prefix) => kind
+                    .mime
+                    .split('/')
+                    .next()
+                    .is_some_and(|top| top == prefix),
+                None => kind.mime == *pattern,
+            },
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            TypeRule::Extension(ext) => format!(".{}", ext),
+            TypeRule::Mime(mime) => mime.clone(),
+        }
+    }
+}
+
+// Type d'un fichier, soit déclaré (nom), soit détecté (contenu)
+#[derive(Debug, Clone)]
+pub struct FileKind {
+    pub extension: Option<String>,
+    pub mime: String,
+}
+
+impl FileKind {
+    pub fn from_name(filename: &str) -> Self {
+        let extension = Path::new(filename)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+        let mime = mime_guess::from_path(filename)
+            .first_raw()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        Self { extension, mime }
+    }
+
+    pub fn from_content(head: &[u8]) -> Option<Self> {
+        infer::get(head).map(|t| Self {
+            extension: Some(t.extension().to_string()),
+            mime: t.mime_type().to_string(),
+        })
+    }
+}
+
+// Verdict de la politique pour un fichier donné
+#[derive(Debug)]
+pub enum PolicyError {
+    Blocked(String),
+    NotAllowed(String),
+}
+
+impl PolicyError {
+    pub fn message(&self) -> String {
+        match self {
+            PolicyError::Blocked(detail) => format!("Type de fichier interdit ({})", detail),
+            PolicyError::NotAllowed(detail) => format!("Type de fichier non autorisé ({})", detail),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UploadPolicy {
+    allowed: Vec<TypeRule>,
+    blocked: Vec<TypeRule>,
+    size_limits: Vec<(TypeRule, usize)>,
+}
+
+impl UploadPolicy {
+    pub fn from_env() -> Self {
+        Self {
+            allowed: parse_rules(&env::var("ALLOWED_TYPES").unwrap_or_default()),
+            blocked: parse_rules(&env::var("BLOCKED_TYPES").unwrap_or_default()),
+            size_limits: parse_size_limits(&env::var("TYPE_SIZE_LIMITS").unwrap_or_default()),
+        }
+    }
+
+    // Vérifie le type déclaré par le nom ET le type détecté dans le contenu :
+    // renommer un .exe en .pdf ne suffit pas à passer le filtre.
+    pub fn check(&self, declared: &FileKind, detected: Option<&FileKind>) -> Result<(), PolicyError> {
+        let kinds: Vec<&FileKind> = std::iter::once(declared).chain(detected).collect();
+
+        for kind in &kinds {
+            if let Some(rule) = self.blocked.iter().find(|r| r.matches(kind)) {
+                return Err(PolicyError::Blocked(describe(kind, rule)));
+            }
+        }
+
+        if !self.allowed.is_empty() {
+            for kind in &kinds {
+                if !self.allowed.iter().any(|r| r.matches(kind)) {
+                    return Err(PolicyError::NotAllowed(kind.mime.clone()));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Limite de taille applicable : la première règle qui correspond au type
+    // détecté dans le contenu (le nom seul si rien n'est détecté, renommer un
+    // fichier ne donne pas une limite plus haute), sinon la limite globale
+    pub fn size_limit(&self, declared: &FileKind, detected: Option<&FileKind>, default: usize) -> usize {
+        let kind = detected.unwrap_or(declared);
+        self.size_limits
+            .iter()
+            .find(|(rule, _)| rule.matches(kind))
+            .map_or(default, |(_, limit)| *limit)
+    }
+
+    // Extensions acceptées pour l'attribut `accept` du formulaire
+    pub fn accept_attribute(&self) -> String {
+        if self.allowed.is_empty() {
+            return "*/*".to_string();
+        }
+        self.allowed
+            .iter()
+            .map(|rule| rule.label())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    pub fn allowed_labels(&self) -> Vec<String> {
+        self.allowed.iter().map(TypeRule::label).collect()
+    }
+
+    pub fn blocked_labels(&self) -> Vec<String> {
+        self.blocked.iter().map(TypeRule::label).collect()
+    }
+
+    pub fn size_limit_labels(&self) -> Vec<(String, usize)> {
+        self.size_limits
+            .iter()
+            .map(|(rule, limit)| (rule.label(), *limit))
+            .collect()
+    }
+}
+
+fn describe(kind: &FileKind, rule: &TypeRule) -> String {
+    match rule {
+        TypeRule::Extension(ext) => format!(".{}", ext),
+        TypeRule::Mime(_) => kind.mime.clone(),
+    }
+}
+
+fn parse_rules(raw: &str) -> Vec<TypeRule> {
+    raw.split(',').filter_map(TypeRule::parse).collect()
+}
+
+// Format: "video/*=500,pdf=20" (tailles en MB)
+fn parse_size_limits(raw: &str) -> Vec<(TypeRule, usize)> {
+    raw.split(',')
+        .filter_map(|entry| {
+            let (rule, size) = entry.split_once('=')?;
+            let size = size.trim().parse::<usize>().ok()?;
+            Some((TypeRule::parse(rule)?, size * 1024 * 1024))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_limit_follows_detected_type() {
+        let policy = UploadPolicy {
+            size_limits: parse_size_limits("video/*=500,txt=1"),
+            ..UploadPolicy::default()
+        };
+        let default = 100 * 1024 * 1024;
+        let exe = FileKind::from_content(b"MZ\x90\x00\x03\x00\x00\x00").unwrap();
+        // Un exécutable renommé en .mp4 garde la limite globale
+        assert_eq!(policy.size_limit(&FileKind::from_name("film.mp4"), Some(&exe), default), default);
+        // Rien de détecté (texte) : le nom décide
+        assert_eq!(policy.size_limit(&FileKind::from_name("notes.txt"), None, default), 1024 * 1024);
+        assert_eq!(policy.size_limit(&FileKind::from_name("film.mp4"), None, default), 500 * 1024 * 1024);
+    }
+}
@@ -0,0 +1,145 @@
+// Quotas de stockage et protection contre le disque plein
+use std::{
+    collections::HashSet,
+    env, fs,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::storage;
+
+// Le parcours complet (dossier d'upload, stockage objet) est refait au plus
+// une fois par USAGE_TTL, ou après une suppression ; entre deux parcours, les
+// publications s'ajoutent au total
+const USAGE_TTL: Duration = Duration::from_secs(300);
+
+struct Used {
+    bytes: u64,
+    computed_at: Instant,
+}
+
+static USED: Mutex<Option<Used>> = Mutex::new(None);
+
+// Contenu publié : ajouté au total sans nouveau parcours
+pub fn stored(bytes: u64) {
+    if let Some(used) = USED.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+        used.bytes += bytes;
+    }
+}
+
+// Contenu supprimé définitivement : le total sera recalculé à la prochaine
+// lecture (un contenu dédupliqué peut rester utilisé ailleurs)
+pub fn released() {
+    *USED.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+// Espace occupé, à appeler hors des workers actix (web::block) : un recalcul
+// parcourt tout le dossier et liste tout le bucket. Le verrou est gardé pendant
+// le parcours, les requêtes simultanées attendent le même résultat.
+fn used(upload_dir: &str) -> u64 {
+    let mut used = USED.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(used) = used.as_ref().filter(|used| used.computed_at.elapsed() < USAGE_TTL) {
+        return used.bytes;
+    }
+    let bytes = dir_size(Path::new(upload_dir)) + external_size(upload_dir);
+    *used = Some(Used { bytes, computed_at: Instant::now() });
+    bytes
+}
+
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    // Taille totale maximale du dossier d'upload (None = illimitée)
+    total: Option<u64>,
+    // Espace disque à toujours laisser libre
+    reserved: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StorageUsage {
+    pub used: u64,
+    pub quota: Option<u64>,
+    pub available: u64,
+}
+
+impl QuotaConfig {
+    pub fn from_env() -> Self {
+        let mb = |name: &str| {
+            env::var(name)
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|v| v * 1024 * 1024)
+        };
+        Self {
+            total: mb("UPLOAD_QUOTA").filter(|&q| q > 0),
+            reserved: mb("RESERVED_SPACE").unwrap_or(100 * 1024 * 1024),
+        }
+    }
+
+    pub fn usage(&self, upload_dir: &str) -> StorageUsage {
+        StorageUsage {
+            used: used(upload_dir),
+            quota: self.total,
+            available: fs4::available_space(upload_dir).unwrap_or(0),
+        }
+    }
+
+    // Nombre d'octets encore acceptables : le plus petit entre le reste du quota
+    // et l'espace libre au-delà de la marge réservée
+    pub fn remaining(&self, upload_dir: &str) -> u64 {
+        let usage = self.usage(upload_dir);
+        let disk = usage.available.saturating_sub(self.reserved);
+        match self.total {
+            Some(total) => disk.min(total.saturating_sub(usage.used)),
+            None => disk,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.total {
+            Some(total) => format!("{} MB", total / (1024 * 1024)),
+            None => "illimité".to_string(),
+        }
+    }
+}
+
+// Contenu stocké hors du dossier d'upload (stockage objet)
+fn external_size(upload_dir: &str) -> u64 {
+    storage::open(upload_dir).external_size().unwrap_or_else(|e| {
+        warn!(error = %e, "⚠️ Erreur calcul de l'espace utilisé dans le stockage");
+        0
+    })
+}
+
+// Taille totale du dossier, sous-dossiers cachés compris (staging, quarantaine...)
+// Les fichiers dédupliqués sont des liens physiques vers le même contenu :
+// chaque contenu n'est compté qu'une fois
+fn dir_size(path: &Path) -> u64 {
+    dir_size_counted(path, &mut HashSet::new())
+}
+
+fn dir_size_counted(path: &Path, seen: &mut HashSet<(u64, u64)>) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => dir_size_counted(&entry.path(), seen),
+            Ok(metadata) if first_link(&metadata, seen) => metadata.len(),
+            _ => 0,
+        })
+        .sum()
+}
+
+#[cfg(unix)]
+fn first_link(metadata: &fs::Metadata, seen: &mut HashSet<(u64, u64)>) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    metadata.nlink() <= 1 || seen.insert((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn first_link(_metadata: &fs::Metadata, _seen: &mut HashSet<(u64, u64)>) -> bool {
+    true
+}
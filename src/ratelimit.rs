@@ -0,0 +1,374 @@
+// Limitation du nombre de requêtes par adresse IP, avant tout handler :
+// un seau par type d'opération, bannissement temporaire après des échecs
+// d'authentification répétés (jeton de propriétaire d'un partage chiffré)
+use std::{
+    collections::HashMap,
+    env,
+    net::IpAddr,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    http::{header, Method},
+    middleware::Next,
+    Error, HttpRequest, HttpResponse,
+};
+use tracing::warn;
+
+use crate::{
+    access,
+    retention::{format_duration, parse_ttl},
+    throttle::ThrottleConfig,
+};
+
+// Taille maximale de la table des clients suivis
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+// Fréquence du nettoyage des clients qui n'ont plus rien à retenir
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    // Vérification d'un secret (jeton de propriétaire)
+    Auth,
+    Upload,
+    Delete,
+    Listing,
+}
+
+impl Operation {
+    const ALL: [Operation; 4] = [Self::Auth, Self::Upload, Self::Delete, Self::Listing];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Auth => "auth",
+            Self::Upload => "upload",
+            Self::Delete => "suppression",
+            Self::Listing => "liste",
+        }
+    }
+
+    // Type d'opération d'une requête, None pour celles qui ne sont pas limitées
+    // (téléchargements, déjà soumis aux limites de débit)
+    fn classify(method: &Method, path: &str) -> Option<Self> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, segments.as_slice()) {
+            (&Method::POST, ["secure", _, "delete"]) => Some(Self::Auth),
+            (&Method::POST, ["upload"] | ["secure"]) => Some(Self::Upload),
+            (&Method::POST, ["delete", _] | ["trash", _, "purge"] | ["trash", "empty"]) => Some(Self::Delete),
+            (&Method::GET, [""] | ["files"] | ["files", _] | ["versions", _] | ["secure", _]) => Some(Self::Listing),
+            _ => None,
+        }
+    }
+}
+
+// Nombre de requêtes autorisées par période (et rafale maximale)
+#[derive(Debug, Clone, Copy)]
+pub struct Rate {
+    requests: u32,
+    period: Duration,
+}
+
+impl Rate {
+    // Formats acceptés : "10/min", "100/h", "5/s", "30/10m"
+    fn parse(value: &str) -> Option<Self> {
+        let (requests, period) = value.trim().split_once('/')?;
+        let requests = requests.trim().parse::<u32>().ok().filter(|&n| n > 0)?;
+        let period = match period.trim() {
+            "s" | "sec" => 1,
+            "min" | "m" => 60,
+            "h" => 3600,
+            other => parse_ttl(other).filter(|&secs| secs > 0)?,
+        };
+        Some(Self { requests, period: Duration::from_secs(period) })
+    }
+
+    fn per_second(&self) -> f64 {
+        self.requests as f64 / self.period.as_secs_f64()
+    }
+
+    // Jetons disponibles à `now` pour un seau laissé à `tokens` à `last`
+    fn refill(&self, tokens: f64, last: Instant, now: Instant) -> f64 {
+        (tokens + now.duration_since(last).as_secs_f64() * self.per_second()).min(self.requests as f64)
+    }
+
+    fn describe(&self) -> String {
+        format!("{} req/{}", self.requests, format_duration(self.period.as_secs()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    // Une limite par type d'opération (None = illimité)
+    rates: [Option<Rate>; 4],
+    // Échecs d'authentification tolérés avant bannissement (None = jamais)
+    ban_after: Option<usize>,
+    ban_duration: Duration,
+}
+
+impl RateLimitConfig {
+    pub fn from_env() -> Self {
+        let disabled = env::var("RATE_LIMIT")
+            .map(|v| matches!(v.to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+            .unwrap_or(false);
+        let rate = |var: &str, default: &str| {
+            if disabled {
+                return None;
+            }
+            let value = env::var(var).unwrap_or_else(|_| default.to_string());
+            match value.to_lowercase().as_str() {
+                "0" | "off" | "none" => None,
+                _ => Rate::parse(&value),
+            }
+        };
+        Self {
+            rates: [
+                rate("RATE_LIMIT_AUTH", "10/min"),
+                rate("RATE_LIMIT_UPLOAD", "120/min"),
+                rate("RATE_LIMIT_DELETE", "120/min"),
+                rate("RATE_LIMIT_LISTING", "600/min"),
+            ],
+            ban_after: env::var("BAN_AFTER")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .or(Some(5))
+                .filter(|&n| n > 0 && !disabled),
+            ban_duration: Duration::from_secs(
+                env::var("BAN_DURATION")
+                    .ok()
+                    .and_then(|v| parse_ttl(&v))
+                    .unwrap_or(15 * 60)
+                    .max(1),
+            ),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let mut rules: Vec<String> = Operation::ALL
+            .iter()
+            .filter_map(|&op| self.rates[op.index()].map(|rate| format!("{} {}", op.label(), rate.describe())))
+            .collect();
+        if let Some(after) = self.ban_after {
+            rules.push(format!("ban {} après {} échecs", format_duration(self.ban_duration.as_secs()), after));
+        }
+        if rules.is_empty() {
+            return "désactivée".to_string();
+        }
+        rules.join(", ")
+    }
+}
+
+#[derive(Default)]
+struct Client {
+    // Jetons restants et date de la dernière mise à jour, par opération
+    buckets: [Option<(f64, Instant)>; 4],
+    failures: Vec<Instant>,
+    banned_until: Option<Instant>,
+    last_seen: Option<Instant>,
+}
+
+impl Client {
+    fn banned(&self, now: Instant) -> bool {
+        self.banned_until.is_some_and(|until| until > now)
+    }
+
+    // Rien à retenir : ni bannissement, ni échec récent, et des seaux pleins.
+    // L'oublier ne change rien pour lui.
+    fn idle(&self, config: &RateLimitConfig, now: Instant) -> bool {
+        let refilled = Operation::ALL.iter().all(|&op| match (config.rates[op.index()], self.buckets[op.index()]) {
+            (Some(rate), Some((tokens, last))) => rate.refill(tokens, last, now) >= rate.requests as f64,
+            _ => true,
+        });
+        refilled
+            && !self.banned(now)
+            && self.failures.iter().all(|&at| now.duration_since(at) >= config.ban_duration)
+    }
+}
+
+#[derive(Default)]
+struct Clients {
+    table: HashMap<IpAddr, Client>,
+    pruned_at: Option<Instant>,
+}
+
+impl Clients {
+    // Entrée d'une adresse. Le nettoyage parcourt toute la table, il n'a donc
+    // lieu qu'une fois par PRUNE_INTERVAL, pas à chaque requête.
+    fn entry(&mut self, config: &RateLimitConfig, ip: IpAddr, now: Instant) -> &mut Client {
+        if self.pruned_at.is_none_or(|at| now.duration_since(at) >= PRUNE_INTERVAL) {
+            self.table.retain(|_, client| !client.idle(config, now));
+            self.pruned_at = Some(now);
+        }
+        if self.table.len() >= MAX_TRACKED_CLIENTS && !self.table.contains_key(&ip) {
+            self.evict(now);
+        }
+        let client = self.table.entry(ip).or_default();
+        client.last_seen = Some(now);
+        client
+    }
+
+    // Table pleine malgré le nettoyage : oublie d'un coup le dixième le moins
+    // récemment vu, adresses bannies en dernier, pour un coût amorti constant
+    fn evict(&mut self, now: Instant) {
+        let mut order: Vec<(bool, Option<Instant>, IpAddr)> = self
+            .table
+            .iter()
+            .map(|(&ip, client)| (client.banned(now), client.last_seen, ip))
+            .collect();
+        let count = (MAX_TRACKED_CLIENTS / 10).clamp(1, order.len());
+        order.select_nth_unstable(count - 1);
+        for (_, _, ip) in &order[..count] {
+            self.table.remove(ip);
+        }
+        warn!(evicted = count, "🚦 Trop de clients suivis, les moins récents sont oubliés");
+    }
+}
+
+static CLIENTS: LazyLock<Mutex<Clients>> = LazyLock::new(Mutex::default);
+
+// Attente avant la prochaine requête autorisée, None si celle-ci passe
+fn check(config: &RateLimitConfig, ip: IpAddr, operation: Option<Operation>) -> Option<Duration> {
+    let now = Instant::now();
+    let mut clients = CLIENTS.lock().unwrap();
+    let client = clients.entry(config, ip, now);
+
+    if let Some(until) = client.banned_until {
+        if until > now {
+            return Some(until - now);
+        }
+        client.banned_until = None;
+    }
+
+    let operation = operation?;
+    let rate = config.rates[operation.index()]?;
+    let (tokens, last) = client.buckets[operation.index()].unwrap_or((rate.requests as f64, now));
+    let tokens = rate.refill(tokens, last, now);
+    if tokens >= 1.0 {
+        client.buckets[operation.index()] = Some((tokens - 1.0, now));
+        None
+    } else {
+        client.buckets[operation.index()] = Some((tokens, now));
+        Some(Duration::from_secs_f64((1.0 - tokens) / rate.per_second()))
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> HttpResponse {
+    let seconds = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+    HttpResponse::TooManyRequests()
+        .insert_header((header::RETRY_AFTER, seconds.to_string()))
+        .json(serde_json::json!({
+            "error": format!("Trop de requêtes, réessayez dans {}", format_duration(seconds))
+        }))
+}
+
+pub async fn middleware(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, Error> {
+    let config = RateLimitConfig::from_env();
+    let ip = access::client_ip(req.request());
+    let operation = Operation::classify(req.method(), req.path());
+    let limited = ip
+        .filter(|&ip| !ThrottleConfig::from_env().is_trusted(ip))
+        .and_then(|ip| check(&config, ip, operation).map(|wait| (ip, wait)));
+
+    if let Some((ip, retry_after)) = limited {
+        warn!(%ip, retry_after_s = retry_after.as_secs_f64().ceil() as u64, "🚦 Requête limitée");
+        return Ok(req.into_response(too_many_requests(retry_after)).map_into_right_body());
+    }
+    next.call(req).await.map(ServiceResponse::map_into_left_body)
+}
+
+// Échec d'authentification : bannit l'adresse après trop d'échecs rapprochés
+pub fn auth_failure(req: &HttpRequest) {
+    let config = RateLimitConfig::from_env();
+    let Some(ban_after) = config.ban_after else {
+        return;
+    };
+    let Some(ip) = access::client_ip(req) else {
+        return;
+    };
+    if ThrottleConfig::from_env().is_trusted(ip) {
+        return;
+    }
+
+    let now = Instant::now();
+    let mut clients = CLIENTS.lock().unwrap();
+    let client = clients.entry(&config, ip, now);
+    client.failures.retain(|&at| now.duration_since(at) < config.ban_duration);
+    client.failures.push(now);
+    if client.failures.len() >= ban_after {
+        client.failures.clear();
+        client.banned_until = Some(now + config.ban_duration);
+        warn!(
+            %ip,
+            duration = %format_duration(config.ban_duration.as_secs()),
+            failures = ban_after,
+            "⛔ Adresse bannie après des échecs d'authentification"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            rates: [None, Rate::parse("10/min"), None, None],
+            ban_after: Some(5),
+            ban_duration: Duration::from_secs(900),
+        }
+    }
+
+    fn ip(n: usize) -> IpAddr {
+        IpAddr::from([10, (n >> 16) as u8, (n >> 8) as u8, n as u8])
+    }
+
+    #[test]
+    fn prune_forgets_only_idle_clients() {
+        let config = config();
+        let start = Instant::now();
+        let mut clients = Clients::default();
+        clients.entry(&config, ip(1), start);
+        clients.entry(&config, ip(3), start).banned_until = Some(start + Duration::from_secs(600));
+        assert_eq!(clients.table.len(), 2);
+
+        // Pas de nettoyage avant PRUNE_INTERVAL
+        let soon = start + Duration::from_secs(10);
+        clients.entry(&config, ip(2), soon).buckets[Operation::Upload.index()] = Some((0.0, soon));
+        clients.entry(&config, ip(4), soon);
+        assert_eq!(clients.table.len(), 4);
+
+        // 10/min : le seau vidé il y a 51 s n'est pas encore plein
+        let later = start + Duration::from_secs(61);
+        clients.entry(&config, ip(5), later);
+        let mut tracked: Vec<IpAddr> = clients.table.keys().copied().collect();
+        tracked.sort();
+        assert_eq!(tracked, vec![ip(2), ip(3), ip(5)]);
+    }
+
+    #[test]
+    fn table_size_is_bounded() {
+        let config = config();
+        let start = Instant::now();
+        let mut clients = Clients::default();
+        clients.entry(&config, ip(0), start).banned_until = Some(start + Duration::from_secs(600));
+        for n in 1..MAX_TRACKED_CLIENTS * 2 {
+            // Seau entamé : aucun client n'est oubliable par le nettoyage
+            let now = start + Duration::from_millis(n as u64);
+            clients.entry(&config, ip(n), now).buckets[Operation::Upload.index()] = Some((0.0, now));
+            assert!(clients.table.len() <= MAX_TRACKED_CLIENTS);
+        }
+        // Les plus récents et l'adresse bannie sont gardés
+        assert!(clients.table.contains_key(&ip(0)));
+        assert!(clients.table.contains_key(&ip(MAX_TRACKED_CLIENTS * 2 - 1)));
+        assert!(!clients.table.contains_key(&ip(1)));
+    }
+}
@@ -0,0 +1,277 @@
+// Expiration automatique des fichiers et politiques de rétention
+use std::{
+    env,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::web;
+use tracing::{error, info};
+
+use crate::{audit, dedup, get_uploaded_files, meta, quota, secure, storage, versions, webhook, FileInfo, trash::{self, TrashConfig}};
+
+#[derive(Debug, Clone)]
+pub struct RetentionConfig {
+    // Durée de vie appliquée quand l'upload n'en précise pas
+    pub default_ttl: Option<u64>,
+    // Durée de vie maximale, y compris pour "never" (None = illimitée)
+    max_ttl: Option<u64>,
+    // Ne garder que les N fichiers les plus récents
+    keep_recent: Option<usize>,
+    // Taille totale maximale, les plus anciens sont supprimés au-delà
+    max_total_size: Option<u64>,
+    // Intervalle entre deux passages du nettoyeur
+    interval: Duration,
+}
+
+impl RetentionConfig {
+    pub fn from_env() -> Self {
+        Self {
+            default_ttl: env::var("DEFAULT_TTL").ok().and_then(|v| parse_ttl(&v)),
+            max_ttl: env::var("MAX_TTL").ok().and_then(|v| parse_ttl(&v)),
+            keep_recent: env::var("RETENTION_KEEP")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n| n > 0),
+            max_total_size: env::var("RETENTION_MAX_SIZE")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .filter(|&mb| mb > 0)
+                .map(|mb| mb * 1024 * 1024),
+            interval: Duration::from_secs(
+                env::var("REAPER_INTERVAL")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(60)
+                    .max(1),
+            ),
+        }
+    }
+
+    // Date d'expiration d'un upload : TTL demandé, sinon TTL par défaut.
+    // "0" ou "never" demande explicitement une conservation illimitée.
+    // Une durée illisible ou trop grande est refusée (message d'erreur) plutôt
+    // que remplacée par une autre.
+    pub fn expiry_for(&self, requested: Option<&str>) -> Result<Option<u64>, String> {
+        self.expiry_at(now(), requested)
+    }
+
+    fn expiry_at(&self, now: u64, requested: Option<&str>) -> Result<Option<u64>, String> {
+        let ttl = match requested.map(str::trim).filter(|v| !v.is_empty()) {
+            Some(value) if matches!(value.to_lowercase().as_str(), "0" | "never") => None,
+            Some(value) => Some(
+                parse_ttl(value)
+                    .filter(|&ttl| now.checked_add(ttl).is_some())
+                    .ok_or_else(|| format!("Durée de conservation invalide: {} (ex: 3600, 90m, 12h, 7d, 2w, never)", value))?,
+            ),
+            None => self.default_ttl,
+        };
+        // Le client ne peut pas dépasser MAX_TTL, pas même avec "never"
+        let ttl = match (ttl, self.max_ttl) {
+            (Some(ttl), Some(max)) => Some(ttl.min(max)),
+            (ttl, max) => ttl.or(max),
+        };
+        Ok(ttl.and_then(|ttl| now.checked_add(ttl)))
+    }
+
+    pub fn describe(&self) -> String {
+        let mut rules = Vec::new();
+        if let Some(ttl) = self.default_ttl {
+            rules.push(format!("TTL {}", format_duration(ttl)));
+        }
+        if let Some(max) = self.max_ttl {
+            rules.push(format!("TTL max {}", format_duration(max)));
+        }
+        if let Some(n) = self.keep_recent {
+            rules.push(format!("{} derniers", n));
+        }
+        if let Some(max) = self.max_total_size {
+            rules.push(format!("max {} MB", max / (1024 * 1024)));
+        }
+        if rules.is_empty() {
+            "aucune".to_string()
+        } else {
+            rules.join(", ")
+        }
+    }
+}
+
+// Formats acceptés : "3600" (secondes), "90m", "12h", "7d", "never"
+pub fn parse_ttl(value: &str) -> Option<u64> {
+    let value = value.trim().to_lowercase();
+    if value == "never" || value == "0" {
+        return None;
+    }
+    let (number, unit) = match value.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&value[..i], c),
+        _ => (value.as_str(), 's'),
+    };
+    let number: u64 = number.parse().ok()?;
+    let factor = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        _ => return None,
+    };
+    number.checked_mul(factor).filter(|&ttl| ttl > 0)
+}
+
+pub fn format_duration(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{} s", seconds),
+        60..=3599 => format!("{} min", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{} jours", seconds / 86400),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// Tâche de fond : supprime périodiquement les fichiers expirés ou en trop
+// et vide la corbeille de ses éléments trop anciens
+pub fn spawn_reaper(upload_dir: String, retention: RetentionConfig, trash: TrashConfig) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(retention.interval);
+        loop {
+            interval.tick().await;
+            let upload_dir = upload_dir.clone();
+            let retention = retention.clone();
+            let trash = trash.clone();
+            let result = web::block(move || {
+                reap(&upload_dir, &retention);
+                trash::purge_expired(&upload_dir, &trash);
+                dedup::sweep(&upload_dir);
+                secure::purge_finished(&upload_dir);
+            })
+            .await;
+            if let Err(e) = result {
+                error!(error = %e, "❌ Erreur tâche de nettoyage");
+            }
+        }
+    });
+}
+
+fn reap(upload_dir: &str, retention: &RetentionConfig) {
+    // Triés du plus récent au plus ancien
+    let files = get_uploaded_files(upload_dir);
+    let now = now();
+
+    let (expired, mut kept): (Vec<_>, Vec<_>) = files
+        .into_iter()
+        .partition(|f| f.expires_at.is_some_and(|at| at <= now));
+    for file in &expired {
+        remove(upload_dir, file, "expiré");
+    }
+
+    if let Some(keep) = retention.keep_recent {
+        if kept.len() > keep {
+            for file in kept.split_off(keep) {
+                remove(upload_dir, &file, "rétention: fichiers les plus récents conservés");
+            }
+        }
+    }
+
+    if let Some(max) = retention.max_total_size {
+        let mut total: u64 = kept.iter().map(|f| f.size).sum();
+        while total > max {
+            let Some(oldest) = kept.pop() else { break };
+            total -= oldest.size;
+            remove(upload_dir, &oldest, "rétention: taille totale dépassée");
+        }
+    }
+}
+
+fn remove(upload_dir: &str, file: &FileInfo, reason: &str) {
+    match storage::open(upload_dir).delete(&file.id) {
+        Ok(_) => {
+            // Suppression définitive : l'historique ne sera plus jamais restauré
+            versions::purge(upload_dir, &file.id);
+            meta::remove(upload_dir, &file.id);
+            dedup::release(upload_dir, file.sha256.as_deref());
+            quota::released();
+            info!(file = %file.name, size = file.size, reason, "⌛ Fichier supprimé");
+            audit::record(upload_dir, audit::Entry::new(audit::Event::Delete, audit::SERVER, &file.name)
+                .id(&file.id)
+                .size(file.size)
+                .detail(reason));
+            webhook::notify(webhook::Event::Expire, audit::SERVER, file, Some(reason));
+        }
+        Err(e) => error!(file = %file.name, error = %e, "❌ Erreur suppression fichier"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(default_ttl: Option<u64>, max_ttl: Option<u64>) -> RetentionConfig {
+        RetentionConfig {
+            default_ttl,
+            max_ttl,
+            keep_recent: None,
+            max_total_size: None,
+            interval: Duration::from_secs(60),
+        }
+    }
+
+    #[test]
+    fn parse_ttl_units() {
+        assert_eq!(parse_ttl("3600"), Some(3600));
+        assert_eq!(parse_ttl("45s"), Some(45));
+        assert_eq!(parse_ttl("90m"), Some(90 * 60));
+        assert_eq!(parse_ttl("12h"), Some(12 * 3600));
+        assert_eq!(parse_ttl("7d"), Some(7 * 86400));
+        assert_eq!(parse_ttl("2w"), Some(14 * 86400));
+        assert_eq!(parse_ttl(" 7D "), Some(7 * 86400));
+    }
+
+    #[test]
+    fn parse_ttl_zero_and_never() {
+        assert_eq!(parse_ttl("0"), None);
+        assert_eq!(parse_ttl("0d"), None);
+        assert_eq!(parse_ttl("never"), None);
+    }
+
+    #[test]
+    fn parse_ttl_garbage() {
+        for value in ["", "d", "abc", "-5m", "1.5h", "10y", "7 d", "h7"] {
+            assert_eq!(parse_ttl(value), None, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn parse_ttl_overflow() {
+        assert_eq!(parse_ttl("99999999999999999d"), None);
+        assert_eq!(parse_ttl("99999999999999999999"), None);
+        assert_eq!(parse_ttl(&format!("{}w", u64::MAX / (7 * 86400) + 1)), None);
+        assert_eq!(parse_ttl(&u64::MAX.to_string()), Some(u64::MAX));
+    }
+
+    #[test]
+    fn expiry_rejects_invalid_or_overflowing_ttl() {
+        let now = 1_700_000_000;
+        let retention = config(Some(3600), None);
+        for value in ["99999999999999999d", &u64::MAX.to_string(), "abc", "10y", "-5m"] {
+            assert!(retention.expiry_at(now, Some(value)).is_err(), "{:?}", value);
+        }
+        assert_eq!(retention.expiry_at(now, Some("")), Ok(Some(now + 3600)));
+        assert_eq!(config(None, None).expiry_at(now, None), Ok(None));
+    }
+
+    #[test]
+    fn expiry_capped_by_max_ttl() {
+        let now = 1_700_000_000;
+        let retention = config(Some(30 * 86400), Some(7 * 86400));
+        assert_eq!(retention.expiry_at(now, Some("1h")), Ok(Some(now + 3600)));
+        assert_eq!(retention.expiry_at(now, Some("52w")), Ok(Some(now + 7 * 86400)));
+        assert_eq!(retention.expiry_at(now, Some("never")), Ok(Some(now + 7 * 86400)));
+        assert_eq!(retention.expiry_at(now, None), Ok(Some(now + 7 * 86400)));
+        assert_eq!(config(None, None).expiry_at(now, Some("never")), Ok(None));
+    }
+}
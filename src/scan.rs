@@ -0,0 +1,463 @@
+// Analyse antivirus des fichiers uploadés (clamd ou commande externe)
+use std::{
+    env, fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    audit, cache,
+    conflict::ConflictPolicy,
+    crypto,
+    dedup::DedupConfig,
+    meta::FileMeta,
+    names, publish_upload,
+    versions::VersioningConfig,
+    webhook, FileInfo,
+};
+
+const SCAN_DIR: &str = ".scan";
+const PENDING_DIR: &str = "pending";
+const QUARANTINE_DIR: &str = "quarantine";
+const VERDICTS_FILE: &str = "verdicts.jsonl";
+// Délai maximal d'une lecture ou écriture sur clamd, au-delà l'analyse échoue
+const CLAMD_TIMEOUT: Duration = Duration::from_secs(120);
+
+#[derive(Debug, Clone)]
+enum ScanMode {
+    Disabled,
+    // Socket unix (chemin) ou adresse TCP "hôte:port" du démon clamd
+    Clamd(String),
+    // Programme + arguments, le chemin du fichier est ajouté en dernier
+    Command(Vec<String>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanConfig {
+    mode: ScanMode,
+}
+
+impl ScanConfig {
+    pub fn from_env() -> Self {
+        let mode = match env::var("SCAN_MODE").unwrap_or_default().to_lowercase().as_str() {
+            "clamd" => ScanMode::Clamd(
+                env::var("CLAMD_SOCKET").unwrap_or_else(|_| "/var/run/clamav/clamd.ctl".to_string()),
+            ),
+            "command" => {
+                let command: Vec<String> = env::var("SCAN_COMMAND")
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect();
+                if command.is_empty() {
+                    warn!("⚠️ SCAN_MODE=command sans SCAN_COMMAND, analyse désactivée");
+                    ScanMode::Disabled
+                } else {
+                    ScanMode::Command(command)
+                }
+            }
+            _ => ScanMode::Disabled,
+        };
+        Self { mode }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !matches!(self.mode, ScanMode::Disabled)
+    }
+
+    pub fn describe(&self) -> String {
+        match &self.mode {
+            ScanMode::Disabled => "désactivée".to_string(),
+            ScanMode::Clamd(addr) => format!("clamd ({})", addr),
+            ScanMode::Command(cmd) => format!("commande ({})", cmd[0]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Clean,
+    Infected,
+    Error,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScanRecord {
+    pub file: String,
+    pub stored_as: Option<String>,
+    pub verdict: Verdict,
+    pub detail: String,
+    pub scanned_at: u64,
+}
+
+fn scan_root(upload_dir: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(SCAN_DIR)
+}
+
+fn pending_dir(upload_dir: &str) -> PathBuf {
+    scan_root(upload_dir).join(PENDING_DIR)
+}
+
+fn quarantine_dir(upload_dir: &str) -> PathBuf {
+    scan_root(upload_dir).join(QUARANTINE_DIR)
+}
+
+// Les fichiers en attente sont nommés d'après un timestamp en nanosecondes, leur nom
+// d'origine est dans le fichier voisin (avant : "<nanos>_<nom>")
+fn display_name(pending_path: &Path) -> String {
+    if let Some(name) = read_pending(pending_path).name {
+        return name;
+    }
+    let pending_name = pending_path.file_name().unwrap().to_string_lossy();
+    pending_name
+        .split_once('_')
+        .map_or(pending_name.to_string(), |(_, name)| name.to_string())
+}
+
+// Ce qu'il faut savoir pour publier un fichier une fois l'analyse terminée,
+// gardé dans un fichier caché à côté du fichier en attente
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct PendingUpload {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    meta: FileMeta,
+    #[serde(default)]
+    conflict: Option<ConflictPolicy>,
+}
+
+fn pending_meta_path(pending_path: &Path) -> PathBuf {
+    let name = pending_path.file_name().unwrap().to_string_lossy();
+    pending_path.with_file_name(format!(".{}.json", name))
+}
+
+fn read_pending(pending_path: &Path) -> PendingUpload {
+    fs::read_to_string(pending_meta_path(pending_path))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+// Publie un fichier en attente avec les options choisies lors de l'upload
+fn release(upload_dir: &str, pending_path: &Path, filename: &str) -> std::io::Result<PathBuf> {
+    let pending = read_pending(pending_path);
+    let policy = pending.conflict.unwrap_or_else(ConflictPolicy::from_env);
+    let result = publish_upload(
+        upload_dir,
+        pending_path,
+        filename,
+        &pending.meta,
+        policy,
+        VersioningConfig::from_env().max_versions,
+        DedupConfig::from_env().enabled,
+    );
+    match &result {
+        Ok(_) => {
+            let _ = fs::remove_file(pending_meta_path(pending_path));
+        }
+        // Conflit de nom avec la stratégie "reject" : le fichier est abandonné
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            let _ = fs::remove_file(pending_path);
+            let _ = fs::remove_file(pending_meta_path(pending_path));
+        }
+        // Autre échec : fichier et métadonnées restent en attente pour être repris
+        Err(_) => {}
+    }
+    result.map(|published| PathBuf::from(upload_dir).join(published.id))
+}
+
+// Place un fichier fraîchement uploadé en attente d'analyse et lance l'analyse
+pub fn submit(upload_dir: &str, scan: &ScanConfig, staged: &Path, filename: &str, file_meta: &FileMeta, conflict: ConflictPolicy) -> std::io::Result<()> {
+    let pending = pending_dir(upload_dir);
+    fs::create_dir_all(&pending)?;
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let pending_path = pending.join(nanos.to_string());
+    let pending_upload = PendingUpload {
+        name: Some(filename.to_string()),
+        meta: file_meta.clone(),
+        conflict: Some(conflict),
+    };
+    let content = serde_json::to_string(&pending_upload).map_err(std::io::Error::other)?;
+    fs::write(pending_meta_path(&pending_path), content)?;
+    fs::rename(staged, &pending_path)?;
+    cache::invalidate_index();
+
+    spawn_scan(upload_dir.to_string(), scan.clone(), pending_path);
+    Ok(())
+}
+
+// Relance l'analyse des fichiers restés en attente (ex: redémarrage du serveur)
+pub fn resume_pending(upload_dir: &str, scan: &ScanConfig) {
+    let Ok(entries) = fs::read_dir(pending_dir(upload_dir)) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if entry.path().is_file() && !name.starts_with('.') {
+            if scan.is_enabled() {
+                spawn_scan(upload_dir.to_string(), scan.clone(), entry.path());
+            } else {
+                // Analyse désactivée depuis : publier directement
+                if let Err(e) = release(upload_dir, &entry.path(), &display_name(&entry.path())) {
+                    error!(pending = %name, error = %e, "❌ Erreur publication fichier en attente");
+                }
+            }
+        }
+    }
+}
+
+fn spawn_scan(upload_dir: String, scan: ScanConfig, pending_path: PathBuf) {
+    actix_web::rt::spawn(async move {
+        let result = web::block(move || scan_and_release(&upload_dir, &scan, &pending_path)).await;
+        if let Err(e) = result {
+            error!(error = %e, "❌ Erreur tâche d'analyse");
+        }
+    });
+}
+
+fn scan_and_release(upload_dir: &str, scan: &ScanConfig, pending_path: &Path) {
+    let pending_name = pending_path.file_name().unwrap().to_string_lossy().to_string();
+    let filename = display_name(pending_path);
+    let uploader = read_pending(pending_path).meta.uploader.unwrap_or_else(|| "inconnu".to_string());
+    let size = fs::metadata(pending_path).map(|m| m.len()).unwrap_or_default();
+
+    let started = Instant::now();
+    let (verdict, detail) = match scan_file(scan, pending_path) {
+        Ok(None) => (Verdict::Clean, String::new()),
+        Ok(Some(signature)) => (Verdict::Infected, signature),
+        Err(e) => (Verdict::Error, e.to_string()),
+    };
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    // Fichier sain : publication dans le dossier partagé. Sinon : quarantaine.
+    let moved = if verdict == Verdict::Clean {
+        release(upload_dir, pending_path, &filename)
+    } else {
+        let destination = quarantine_dir(upload_dir).join(&pending_name);
+        let _ = fs::remove_file(pending_meta_path(pending_path));
+        fs::create_dir_all(quarantine_dir(upload_dir))
+            .and_then(|_| fs::rename(pending_path, &destination))
+            .map(|_| destination)
+    };
+
+    cache::invalidate_index();
+    let stored_as = match moved {
+        Ok(destination) => destination.file_name().map(|n| n.to_string_lossy().to_string()),
+        Err(e) => {
+            error!(file = %filename, error = %e, "❌ Erreur déplacement fichier analysé");
+            None
+        }
+    };
+
+    match verdict {
+        Verdict::Clean => info!(file = %filename, size, duration_ms, "✅ Analyse OK"),
+        Verdict::Infected => warn!(file = %filename, size, duration_ms, signature = %detail, "☣️ Fichier infecté mis en quarantaine"),
+        Verdict::Error => error!(file = %filename, size, duration_ms, error = %detail, "⚠️ Analyse impossible, fichier mis en quarantaine"),
+    }
+
+    let entry = audit::Entry::new(audit::Event::Upload, &uploader, &filename).size(size);
+    audit::record(upload_dir, match (&verdict, &stored_as) {
+        (Verdict::Clean, Some(id)) => entry.id(id).detail("analyse OK"),
+        (Verdict::Clean, None) => entry.failed("erreur de publication"),
+        _ => entry.failed(&format!("quarantaine: {}", detail)),
+    });
+    if let (Verdict::Clean, Some(id)) = (&verdict, &stored_as) {
+        if let Some(file) = webhook::lookup(webhook::Event::Upload, upload_dir, id) {
+            webhook::notify(webhook::Event::Upload, &uploader, &file, Some("analyse OK"));
+        }
+    }
+
+    record_verdict(upload_dir, &ScanRecord {
+        file: filename,
+        stored_as,
+        verdict,
+        detail,
+        scanned_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    });
+}
+
+fn record_verdict(upload_dir: &str, record: &ScanRecord) {
+    let path = scan_root(upload_dir).join(VERDICTS_FILE);
+    let result = serde_json::to_string(record)
+        .map_err(std::io::Error::other)
+        .and_then(|line| {
+            let mut f = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+            writeln!(f, "{}", line)
+        });
+    if let Err(e) = result {
+        error!(error = %e, "❌ Erreur enregistrement verdict");
+    }
+}
+
+// Ok(None) si le fichier est sain, Ok(Some(signature)) s'il est infecté
+fn scan_file(scan: &ScanConfig, path: &Path) -> std::io::Result<Option<String>> {
+    match &scan.mode {
+        ScanMode::Disabled => Ok(None),
+        ScanMode::Clamd(addr) => scan_with_clamd(addr, path),
+        ScanMode::Command(command) if crypto::enabled() => scan_decrypted(command, path),
+        ScanMode::Command(command) => scan_with_command(command, path),
+    }
+}
+
+// Le programme d'analyse lit un fichier : copie déchiffrée le temps de l'analyse
+fn scan_decrypted(command: &[String], path: &Path) -> std::io::Result<Option<String>> {
+    let name = path.file_name().unwrap().to_string_lossy();
+    let plain = path.with_file_name(format!(".{}.scan", name));
+    let result = fs::File::create(&plain)
+        .and_then(|mut file| std::io::copy(&mut crypto::open_file(path)?, &mut file))
+        .and_then(|_| scan_with_command(command, &plain));
+    let _ = fs::remove_file(&plain);
+    result
+}
+
+fn scan_with_command(command: &[String], path: &Path) -> std::io::Result<Option<String>> {
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(path)
+        .output()?;
+
+    // Convention clamscan : 0 = sain, 1 = infecté, autre = erreur
+    match output.status.code() {
+        Some(0) => Ok(None),
+        Some(1) => {
+            let report = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            Ok(Some(if report.is_empty() { "infecté".to_string() } else { report }))
+        }
+        _ => Err(std::io::Error::other(format!(
+            "code de sortie {:?}: {}",
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))),
+    }
+}
+
+fn scan_with_clamd(addr: &str, path: &Path) -> std::io::Result<Option<String>> {
+    let reply = if addr.starts_with('/') {
+        #[cfg(unix)]
+        {
+            let stream = std::os::unix::net::UnixStream::connect(addr)?;
+            stream.set_read_timeout(Some(CLAMD_TIMEOUT))?;
+            stream.set_write_timeout(Some(CLAMD_TIMEOUT))?;
+            clamd_instream(stream, path).map_err(clamd_timeout)?
+        }
+        #[cfg(not(unix))]
+        {
+            return Err(std::io::Error::other("socket unix non supporté sur cette plateforme"));
+        }
+    } else {
+        let stream = std::net::TcpStream::connect_timeout(&resolve_clamd(addr)?, CLAMD_TIMEOUT)?;
+        stream.set_read_timeout(Some(CLAMD_TIMEOUT))?;
+        stream.set_write_timeout(Some(CLAMD_TIMEOUT))?;
+        clamd_instream(stream, path).map_err(clamd_timeout)?
+    };
+
+    // Réponses : "stream: OK" ou "stream: <signature> FOUND"
+    let reply = reply.trim_end_matches('\0').trim();
+    if reply.ends_with("OK") {
+        Ok(None)
+    } else if let Some(found) = reply.strip_suffix("FOUND") {
+        Ok(Some(found.trim_start_matches("stream:").trim().to_string()))
+    } else {
+        Err(std::io::Error::other(format!("réponse clamd inattendue: {}", reply)))
+    }
+}
+
+fn resolve_clamd(addr: &str) -> std::io::Result<std::net::SocketAddr> {
+    use std::net::ToSocketAddrs;
+    addr.to_socket_addrs()?
+        .next()
+        .ok_or_else(|| std::io::Error::other(format!("adresse clamd introuvable: {}", addr)))
+}
+
+// Un clamd bloqué ne doit pas immobiliser un thread : le délai dépassé est une erreur d'analyse
+fn clamd_timeout(e: std::io::Error) -> std::io::Error {
+    match e.kind() {
+        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut => std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!("clamd ne répond pas après {}s", CLAMD_TIMEOUT.as_secs()),
+        ),
+        _ => e,
+    }
+}
+
+// Protocole INSTREAM : le contenu est envoyé par blocs préfixés de leur taille,
+// clamd n'a donc pas besoin d'accéder au dossier d'upload
+fn clamd_instream<S: Read + Write>(mut stream: S, path: &Path) -> std::io::Result<String> {
+    stream.write_all(b"zINSTREAM\0")?;
+    let mut file = crypto::open_file(path)?;
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        stream.write_all(&(n as u32).to_be_bytes())?;
+        stream.write_all(&buf[..n])?;
+    }
+    stream.write_all(&0u32.to_be_bytes())?;
+    stream.flush()?;
+
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply)?;
+    Ok(reply)
+}
+
+// Fichiers en cours d'analyse, affichés avec l'état "analyse en cours"
+pub fn pending_files(upload_dir: &str) -> Vec<FileInfo> {
+    let mut files = Vec::new();
+    if let Ok(entries) = fs::read_dir(pending_dir(upload_dir)) {
+        for entry in entries.flatten() {
+            if let Ok(metadata) = entry.metadata() {
+                let pending_name = entry.file_name().to_string_lossy().to_string();
+                if metadata.is_file() && !pending_name.starts_with('.') {
+                    let name = display_name(&entry.path());
+                    let file_type = names::file_type(&name);
+                    let file_meta = read_pending(&entry.path()).meta;
+                    files.push(FileInfo {
+                        id: pending_name,
+                        name,
+                        size: crypto::file_size(&entry.path()).unwrap_or(metadata.len()),
+                        uploaded_at: file_meta.uploaded_at.unwrap_or_else(|| {
+                            metadata
+                                .modified()
+                                .unwrap_or(SystemTime::UNIX_EPOCH)
+                                .duration_since(UNIX_EPOCH)
+                                .unwrap()
+                                .as_secs()
+                        }),
+                        file_type,
+                        expires_at: None,
+                        uploader: file_meta.uploader,
+                        sha256: file_meta.sha256,
+                        blake3: file_meta.blake3,
+                        verified_at: file_meta.verified_at,
+                        corrupt: file_meta.corrupt,
+                        mime: file_meta.mime,
+                        description: file_meta.description,
+                        tags: file_meta.tags,
+                    });
+                }
+            }
+        }
+    }
+    files
+}
+
+pub fn quarantined_count(upload_dir: &str) -> usize {
+    fs::read_dir(quarantine_dir(upload_dir))
+        .map(|entries| entries.flatten().filter(|e| e.path().is_file()).count())
+        .unwrap_or(0)
+}
@@ -0,0 +1,548 @@
+// Partages chiffrés de bout en bout : tout le chiffrement se fait ici, dans le navigateur.
+//
+// Clé AES-GCM 256 bits tirée au hasard pour chaque partage, transmise uniquement dans
+// le fragment du lien (#...), que le navigateur n'envoie jamais au serveur.
+// Contenu : blocs de 64 Ko chiffrés séparément (contenu + tag de 16 octets). L'IV d'un
+// bloc vaut 0 sauf ses 8 derniers octets (numéro du bloc) et son premier octet, à 1
+// pour le dernier bloc : un fichier tronqué ou réordonné ne se déchiffre pas.
+// Métadonnées (nom, type, taille) : JSON chiffré avec l'IV 02 00 .. 00, en base64url.
+'use strict';
+
+const SECURE_CHUNK = 64 * 1024;
+const SECURE_TAG = 16;
+
+function b64urlEncode(bytes) {
+    let binary = '';
+    for (const b of bytes) binary += String.fromCharCode(b);
+    return btoa(binary).replace(/\+/g, '-').replace(/\//g, '_').replace(/=+$/, '');
+}
+
+function b64urlDecode(text) {
+    const binary = atob(text.replace(/-/g, '+').replace(/_/g, '/'));
+    return Uint8Array.from(binary, c => c.charCodeAt(0));
+}
+
+function secureIv(index, last) {
+    const iv = new Uint8Array(12);
+    const view = new DataView(iv.buffer);
+    view.setUint32(4, Math.floor(index / 0x100000000));
+    view.setUint32(8, index >>> 0);
+    iv[0] = last ? 1 : 0;
+    return iv;
+}
+
+const META_IV = Uint8Array.of(2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+
+// WebCrypto n'existe que dans un contexte sécurisé (HTTPS ou localhost)
+function secureAvailable() {
+    return !!(window.crypto && window.crypto.subtle);
+}
+
+async function secureEncrypt(file, onProgress) {
+    const key = await crypto.subtle.generateKey({ name: 'AES-GCM', length: 256 }, true, ['encrypt', 'decrypt']);
+    const rawKey = new Uint8Array(await crypto.subtle.exportKey('raw', key));
+    const info = JSON.stringify({ name: file.name, type: file.type, size: file.size });
+    const meta = new Uint8Array(await crypto.subtle.encrypt(
+        { name: 'AES-GCM', iv: META_IV }, key, new TextEncoder().encode(info)));
+
+    // Un fichier vide donne quand même un bloc (le tag seul)
+    const count = Math.max(1, Math.ceil(file.size / SECURE_CHUNK));
+    const parts = [];
+    for (let i = 0; i < count; i++) {
+        const plain = await file.slice(i * SECURE_CHUNK, (i + 1) * SECURE_CHUNK).arrayBuffer();
+        parts.push(new Uint8Array(await crypto.subtle.encrypt(
+            { name: 'AES-GCM', iv: secureIv(i, i === count - 1) }, key, plain)));
+        if (onProgress) onProgress((i + 1) / count);
+    }
+    return { key: b64urlEncode(rawKey), meta: b64urlEncode(meta), blob: new Blob(parts) };
+}
+
+async function secureImportKey(text) {
+    const raw = b64urlDecode(text);
+    if (raw.length !== 32) throw new Error('Clé invalide dans le lien');
+    return crypto.subtle.importKey('raw', raw, { name: 'AES-GCM' }, false, ['decrypt']);
+}
+
+async function secureDecryptMeta(key, meta) {
+    const plain = await crypto.subtle.decrypt({ name: 'AES-GCM', iv: META_IV }, key, b64urlDecode(meta));
+    return JSON.parse(new TextDecoder().decode(plain));
+}
+
+async function secureDecrypt(key, data, onProgress) {
+    const sealed = SECURE_CHUNK + SECURE_TAG;
+    const count = Math.max(1, Math.ceil(data.byteLength / sealed));
+    const parts = [];
+    for (let i = 0; i < count; i++) {
+        const chunk = data.subarray(i * sealed, (i + 1) * sealed);
+        parts.push(new Uint8Array(await crypto.subtle.decrypt(
+            { name: 'AES-GCM', iv: secureIv(i, i === count - 1) }, key, chunk)));
+        if (onProgress) onProgress((i + 1) / count);
+    }
+    return parts;
+}
+
+// --- QR code (mode octet, correction M au minimum, comme les QR codes du serveur) ---
+// Le lien contient la clé : il ne peut pas être envoyé au serveur pour générer l'image.
+
+const QR_ECC = {
+    // Niveau: [bits de format, codes de correction par bloc, nombre de blocs] par version
+    L: [1,
+        [7, 10, 15, 20, 26, 18, 20, 24, 30, 18, 20, 24, 26, 30, 22, 24, 28, 30, 28, 28, 28, 28, 30, 30, 26, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+        [1, 1, 1, 1, 1, 2, 2, 2, 2, 4, 4, 4, 4, 4, 6, 6, 6, 6, 7, 8, 8, 9, 9, 10, 12, 12, 12, 13, 14, 15, 16, 17, 18, 19, 19, 20, 21, 22, 24, 25]],
+    M: [0,
+        [10, 16, 26, 18, 24, 16, 18, 22, 22, 26, 30, 22, 22, 24, 24, 28, 28, 26, 26, 26, 26, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28, 28],
+        [1, 1, 1, 2, 2, 4, 4, 4, 5, 5, 5, 8, 9, 9, 10, 10, 11, 13, 14, 16, 17, 17, 18, 20, 21, 23, 25, 26, 28, 29, 31, 33, 35, 37, 38, 40, 43, 45, 47, 49]],
+    Q: [3,
+        [13, 22, 18, 26, 18, 24, 18, 22, 20, 24, 28, 26, 24, 20, 30, 24, 28, 28, 26, 30, 28, 30, 30, 30, 30, 28, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+        [1, 1, 2, 2, 4, 4, 6, 6, 8, 8, 8, 10, 12, 16, 12, 17, 16, 18, 21, 20, 23, 23, 25, 27, 29, 34, 34, 35, 38, 40, 43, 45, 48, 51, 53, 56, 59, 62, 65, 68]],
+    H: [2,
+        [17, 28, 22, 16, 22, 28, 26, 26, 24, 28, 24, 28, 22, 24, 24, 30, 28, 28, 26, 28, 30, 24, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30],
+        [1, 1, 2, 4, 4, 4, 5, 6, 8, 8, 11, 11, 16, 16, 18, 16, 19, 21, 25, 25, 25, 34, 30, 32, 35, 37, 40, 42, 45, 48, 51, 54, 57, 60, 63, 66, 70, 74, 77, 81]],
+};
+
+function qrRawModules(ver) {
+    let result = (16 * ver + 128) * ver + 64;
+    if (ver >= 2) {
+        const align = Math.floor(ver / 7) + 2;
+        result -= (25 * align - 10) * align - 55;
+        if (ver >= 7) result -= 36;
+    }
+    return result;
+}
+
+function qrDataCodewords(ver, ecl) {
+    return Math.floor(qrRawModules(ver) / 8) - QR_ECC[ecl][1][ver - 1] * QR_ECC[ecl][2][ver - 1];
+}
+
+function qrMultiply(x, y) {
+    let z = 0;
+    for (let i = 7; i >= 0; i--) {
+        z = (z << 1) ^ ((z >>> 7) * 0x11D);
+        z ^= ((y >>> i) & 1) * x;
+    }
+    return z;
+}
+
+function qrDivisor(degree) {
+    const result = new Array(degree).fill(0);
+    result[degree - 1] = 1;
+    let root = 1;
+    for (let i = 0; i < degree; i++) {
+        for (let j = 0; j < result.length; j++) {
+            result[j] = qrMultiply(result[j], root);
+            if (j + 1 < result.length) result[j] ^= result[j + 1];
+        }
+        root = qrMultiply(root, 0x02);
+    }
+    return result;
+}
+
+function qrRemainder(data, divisor) {
+    const result = divisor.map(() => 0);
+    for (const b of data) {
+        const factor = b ^ result.shift();
+        result.push(0);
+        divisor.forEach((coef, i) => result[i] ^= qrMultiply(coef, factor));
+    }
+    return result;
+}
+
+function qrEncode(text) {
+    const bytes = new TextEncoder().encode(text);
+    let ver = 1;
+    for (; ; ver++) {
+        if (ver > 40) throw new Error('Lien trop long pour un QR code');
+        const countBits = ver <= 9 ? 8 : 16;
+        if (4 + countBits + bytes.length * 8 <= qrDataCodewords(ver, 'M') * 8) break;
+    }
+    const countBits = ver <= 9 ? 8 : 16;
+    const used = 4 + countBits + bytes.length * 8;
+    // Meilleure correction possible sans changer de version
+    let ecl = 'M';
+    for (const level of ['Q', 'H']) {
+        if (used <= qrDataCodewords(ver, level) * 8) ecl = level;
+    }
+
+    const bits = [];
+    const push = (value, len) => {
+        for (let i = len - 1; i >= 0; i--) bits.push((value >>> i) & 1);
+    };
+    push(4, 4);
+    push(bytes.length, countBits);
+    for (const b of bytes) push(b, 8);
+    const capacity = qrDataCodewords(ver, ecl) * 8;
+    push(0, Math.min(4, capacity - bits.length));
+    push(0, (8 - bits.length % 8) % 8);
+    for (let pad = 0xEC; bits.length < capacity; pad ^= 0xEC ^ 0x11) push(pad, 8);
+    const data = [];
+    for (let i = 0; i < bits.length; i += 8) data.push(parseInt(bits.slice(i, i + 8).join(''), 2));
+
+    // Blocs de correction d'erreurs, entrelacés
+    const numBlocks = QR_ECC[ecl][2][ver - 1];
+    const eccLen = QR_ECC[ecl][1][ver - 1];
+    const rawCodewords = Math.floor(qrRawModules(ver) / 8);
+    const numShort = numBlocks - rawCodewords % numBlocks;
+    const shortLen = Math.floor(rawCodewords / numBlocks);
+    const divisor = qrDivisor(eccLen);
+    const blocks = [];
+    for (let i = 0, k = 0; i < numBlocks; i++) {
+        const block = data.slice(k, k + shortLen - eccLen + (i < numShort ? 0 : 1));
+        k += block.length;
+        const ecc = qrRemainder(block, divisor);
+        if (i < numShort) block.push(0);
+        blocks.push(block.concat(ecc));
+    }
+    const codewords = [];
+    for (let i = 0; i < blocks[0].length; i++) {
+        blocks.forEach((block, j) => {
+            if (i !== shortLen - eccLen || j >= numShort) codewords.push(block[i]);
+        });
+    }
+
+    const size = ver * 4 + 17;
+    const modules = Array.from({ length: size }, () => new Array(size).fill(false));
+    const isFunction = Array.from({ length: size }, () => new Array(size).fill(false));
+    const setFunction = (x, y, dark) => {
+        modules[y][x] = dark;
+        isFunction[y][x] = true;
+    };
+
+    const drawFormat = (mask) => {
+        const value = QR_ECC[ecl][0] << 3 | mask;
+        let rem = value;
+        for (let i = 0; i < 10; i++) rem = (rem << 1) ^ ((rem >>> 9) * 0x537);
+        const format = (value << 10 | rem) ^ 0x5412;
+        const bit = (i) => ((format >>> i) & 1) !== 0;
+        for (let i = 0; i <= 5; i++) setFunction(8, i, bit(i));
+        setFunction(8, 7, bit(6));
+        setFunction(8, 8, bit(7));
+        setFunction(7, 8, bit(8));
+        for (let i = 9; i < 15; i++) setFunction(14 - i, 8, bit(i));
+        for (let i = 0; i < 8; i++) setFunction(size - 1 - i, 8, bit(i));
+        for (let i = 8; i < 15; i++) setFunction(8, size - 15 + i, bit(i));
+        setFunction(8, size - 8, true);
+    };
+
+    // Motifs fixes : synchronisation, repères, alignement, format et version
+    for (let i = 0; i < size; i++) {
+        setFunction(6, i, i % 2 === 0);
+        setFunction(i, 6, i % 2 === 0);
+    }
+    for (const [cx, cy] of [[3, 3], [size - 4, 3], [3, size - 4]]) {
+        for (let dy = -4; dy <= 4; dy++) {
+            for (let dx = -4; dx <= 4; dx++) {
+                const dist = Math.max(Math.abs(dx), Math.abs(dy));
+                const x = cx + dx, y = cy + dy;
+                if (x >= 0 && x < size && y >= 0 && y < size) setFunction(x, y, dist !== 2 && dist !== 4);
+            }
+        }
+    }
+    if (ver > 1) {
+        const count = Math.floor(ver / 7) + 2;
+        const step = Math.floor((ver * 8 + count * 3 + 5) / (count * 4 - 4)) * 2;
+        const positions = [6];
+        for (let pos = size - 7; positions.length < count; pos -= step) positions.splice(1, 0, pos);
+        positions.forEach((x, i) => positions.forEach((y, j) => {
+            if ((i === 0 && j === 0) || (i === 0 && j === count - 1) || (i === count - 1 && j === 0)) return;
+            for (let dy = -2; dy <= 2; dy++) {
+                for (let dx = -2; dx <= 2; dx++) setFunction(x + dx, y + dy, Math.max(Math.abs(dx), Math.abs(dy)) !== 1);
+            }
+        }));
+    }
+    drawFormat(0);
+    if (ver >= 7) {
+        let rem = ver;
+        for (let i = 0; i < 12; i++) rem = (rem << 1) ^ ((rem >>> 11) * 0x1F25);
+        const value = ver << 12 | rem;
+        for (let i = 0; i < 18; i++) {
+            const dark = ((value >>> i) & 1) !== 0;
+            const a = size - 11 + i % 3, b = Math.floor(i / 3);
+            setFunction(a, b, dark);
+            setFunction(b, a, dark);
+        }
+    }
+
+    // Placement des données en zigzag
+    let i = 0;
+    for (let right = size - 1; right >= 1; right -= 2) {
+        if (right === 6) right = 5;
+        for (let vert = 0; vert < size; vert++) {
+            for (let j = 0; j < 2; j++) {
+                const x = right - j;
+                const y = ((right + 1) & 2) === 0 ? size - 1 - vert : vert;
+                if (!isFunction[y][x] && i < codewords.length * 8) {
+                    modules[y][x] = ((codewords[i >>> 3] >>> (7 - (i & 7))) & 1) !== 0;
+                    i++;
+                }
+            }
+        }
+    }
+
+    const masks = [
+        (x, y) => (x + y) % 2 === 0,
+        (x, y) => y % 2 === 0,
+        (x, y) => x % 3 === 0,
+        (x, y) => (x + y) % 3 === 0,
+        (x, y) => (Math.floor(x / 3) + Math.floor(y / 2)) % 2 === 0,
+        (x, y) => x * y % 2 + x * y % 3 === 0,
+        (x, y) => (x * y % 2 + x * y % 3) % 2 === 0,
+        (x, y) => ((x + y) % 2 + x * y % 3) % 2 === 0,
+    ];
+    const applyMask = (mask) => {
+        for (let y = 0; y < size; y++) {
+            for (let x = 0; x < size; x++) {
+                if (!isFunction[y][x] && masks[mask](x, y)) modules[y][x] = !modules[y][x];
+            }
+        }
+    };
+
+    // Masque retenu : celui de plus faible pénalité
+    let best = 0, bestPenalty = Infinity;
+    for (let mask = 0; mask < 8; mask++) {
+        applyMask(mask);
+        drawFormat(mask);
+        const penalty = qrPenalty(modules);
+        if (penalty < bestPenalty) {
+            best = mask;
+            bestPenalty = penalty;
+        }
+        applyMask(mask);
+    }
+    applyMask(best);
+    drawFormat(best);
+    return modules;
+}
+
+function qrPenalty(modules) {
+    const size = modules.length;
+    let result = 0;
+    const addHistory = (run, history) => {
+        if (history[0] === 0) run += size;
+        history.pop();
+        history.unshift(run);
+    };
+    const countPatterns = (h) => {
+        const n = h[1];
+        const core = n > 0 && h[2] === n && h[3] === n * 3 && h[4] === n && h[5] === n;
+        return (core && h[0] >= n * 4 && h[6] >= n ? 1 : 0) + (core && h[6] >= n * 4 && h[0] >= n ? 1 : 0);
+    };
+    const line = (get) => {
+        let color = false, run = 0;
+        const history = [0, 0, 0, 0, 0, 0, 0];
+        for (let i = 0; i < size; i++) {
+            if (get(i) === color) {
+                run++;
+                if (run === 5) result += 3;
+                else if (run > 5) result++;
+            } else {
+                addHistory(run, history);
+                if (!color) result += countPatterns(history) * 40;
+                color = get(i);
+                run = 1;
+            }
+        }
+        if (color) {
+            addHistory(run, history);
+            run = 0;
+        }
+        addHistory(run + size, history);
+        result += countPatterns(history) * 40;
+    };
+    for (let y = 0; y < size; y++) line((x) => modules[y][x]);
+    for (let x = 0; x < size; x++) line((y) => modules[y][x]);
+
+    let dark = 0;
+    for (let y = 0; y < size; y++) {
+        for (let x = 0; x < size; x++) {
+            if (modules[y][x]) dark++;
+            if (y < size - 1 && x < size - 1) {
+                const c = modules[y][x];
+                if (c === modules[y][x + 1] && c === modules[y + 1][x] && c === modules[y + 1][x + 1]) result += 3;
+            }
+        }
+    }
+    const total = size * size;
+    result += (Math.ceil(Math.abs(dark * 20 - total * 10) / total) - 1) * 10;
+    return result;
+}
+
+function qrSvg(text, pixels) {
+    const modules = qrEncode(text);
+    const size = modules.length;
+    let path = '';
+    modules.forEach((row, y) => row.forEach((dark, x) => {
+        if (dark) path += `M${x} ${y}h1v1h-1z`;
+    }));
+    return `<svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -1 ${size + 2} ${size + 2}" width="${pixels}" height="${pixels}" shape-rendering="crispEdges">`
+        + `<rect x="-1" y="-1" width="${size + 2}" height="${size + 2}" fill="#fff"/><path d="${path}" fill="#000"/></svg>`;
+}
+
+// --- Envoi (page principale) ---
+
+function secureShares() {
+    try {
+        return JSON.parse(localStorage.getItem('secureShares') || '[]');
+    } catch (_) {
+        return [];
+    }
+}
+
+function saveSecureShares(shares) {
+    localStorage.setItem('secureShares', JSON.stringify(shares));
+}
+
+function renderSecureShares() {
+    const list = document.getElementById('secureList');
+    if (!list) return;
+    const now = Date.now() / 1000;
+    const shares = secureShares().filter(s => s.expires_at > now);
+    saveSecureShares(shares);
+    list.replaceChildren();
+    for (const share of shares) {
+        const item = document.createElement('li');
+        const link = document.createElement('a');
+        link.href = share.url;
+        link.textContent = share.name;
+        const remove = document.createElement('button');
+        remove.className = 'btn btn-delete';
+        remove.textContent = '🗑️';
+        remove.title = 'Supprimer ce partage';
+        remove.onclick = () => deleteSecureShare(share.id);
+        item.append('🔒 ', link, ' ', remove);
+        list.append(item);
+    }
+}
+
+async function deleteSecureShare(id) {
+    if (!confirm('Supprimer ce partage chiffré ?')) return;
+    const share = secureShares().find(s => s.id === id);
+    const response = await fetch('/secure/' + id + '/delete', {
+        method: 'POST',
+        headers: { 'X-Owner-Token': share ? share.owner_token : '' },
+    });
+    if (response.ok || response.status === 404) {
+        saveSecureShares(secureShares().filter(s => s.id !== id));
+        renderSecureShares();
+    }
+}
+
+async function secureUpload() {
+    const input = document.getElementById('secureFile');
+    const status = document.getElementById('secureStatus');
+    const result = document.getElementById('secureResult');
+    const file = input.files[0];
+    if (!file) return;
+    result.style.display = 'none';
+    if (!secureAvailable()) {
+        status.textContent = '❌ Le chiffrement dans le navigateur nécessite HTTPS (ou localhost)';
+        return;
+    }
+
+    try {
+        status.textContent = '🔐 Chiffrement…';
+        const encrypted = await secureEncrypt(file, p => status.textContent = `🔐 Chiffrement… ${Math.round(p * 100)}%`);
+        const form = new FormData();
+        form.append('meta', encrypted.meta);
+        form.append('ttl', document.getElementById('secureTtl').value);
+        form.append('downloads', document.getElementById('secureDownloads').value);
+        form.append('blob', encrypted.blob, 'blob');
+
+        const response = await new Promise((resolve, reject) => {
+            const xhr = new XMLHttpRequest();
+            xhr.upload.addEventListener('progress', (e) => {
+                if (e.lengthComputable) status.textContent = `⬆️ Envoi… ${Math.round(e.loaded / e.total * 100)}%`;
+            });
+            xhr.addEventListener('load', () => resolve(xhr));
+            xhr.addEventListener('error', () => reject(new Error('Erreur réseau lors de l\'envoi')));
+            xhr.open('POST', '/secure');
+            xhr.send(form);
+        });
+        const body = JSON.parse(response.responseText);
+        if (response.status !== 200) throw new Error(body.error || 'Erreur lors de l\'envoi');
+
+        const url = `${location.origin}/s/${body.id}#${encrypted.key}`;
+        saveSecureShares(secureShares().concat([{
+            id: body.id, name: file.name, url, owner_token: body.owner_token, expires_at: body.expires_at,
+        }]));
+        renderSecureShares();
+        status.textContent = '✅ Partage créé : seul ce lien permet de lire le fichier';
+        document.getElementById('secureLink').value = url;
+        document.getElementById('secureQr').innerHTML = qrSvg(url, 200);
+        result.style.display = 'block';
+        if (typeof pauseRefresh === 'function') pauseRefresh();
+    } catch (e) {
+        status.textContent = '❌ ' + e.message;
+    }
+    input.value = '';
+}
+
+function copySecureLink() {
+    const link = document.getElementById('secureLink');
+    link.select();
+    navigator.clipboard ? navigator.clipboard.writeText(link.value) : document.execCommand('copy');
+}
+
+// --- Réception (page /s/<id>) ---
+
+async function secureDownload(id) {
+    const status = document.getElementById('secureStatus');
+    const button = document.getElementById('secureButton');
+    const keyText = location.hash.slice(1);
+    if (!keyText) {
+        status.textContent = '❌ Lien incomplet : la clé de déchiffrement (après #) est manquante';
+        return;
+    }
+    if (!secureAvailable()) {
+        status.textContent = '❌ Le déchiffrement dans le navigateur nécessite HTTPS (ou localhost)';
+        return;
+    }
+
+    try {
+        button.disabled = true;
+        const key = await secureImportKey(keyText);
+        const info = await (await fetch('/secure/' + id)).json();
+        if (info.error) throw new Error(info.error);
+        const meta = await secureDecryptMeta(key, info.meta).catch(() => {
+            throw new Error('Clé incorrecte pour ce partage');
+        });
+
+        const data = await new Promise((resolve, reject) => {
+            const xhr = new XMLHttpRequest();
+            xhr.responseType = 'arraybuffer';
+            xhr.addEventListener('progress', (e) => {
+                if (e.lengthComputable) status.textContent = `⬇️ Téléchargement… ${Math.round(e.loaded / e.total * 100)}%`;
+            });
+            xhr.addEventListener('load', () => xhr.status === 200
+                ? resolve(new Uint8Array(xhr.response))
+                : reject(new Error('Ce partage n\'est plus disponible')));
+            xhr.addEventListener('error', () => reject(new Error('Erreur réseau lors du téléchargement')));
+            xhr.open('GET', '/secure/' + id + '/blob');
+            xhr.send();
+        });
+        const parts = await secureDecrypt(key, data, p => status.textContent = `🔓 Déchiffrement… ${Math.round(p * 100)}%`)
+            .catch(() => {
+                throw new Error('Contenu altéré ou incomplet, déchiffrement impossible');
+            });
+
+        const link = document.createElement('a');
+        link.href = URL.createObjectURL(new Blob(parts, { type: meta.type || 'application/octet-stream' }));
+        link.download = meta.name || 'fichier';
+        link.click();
+        status.textContent = `✅ ${meta.name} déchiffré`;
+    } catch (e) {
+        status.textContent = '❌ ' + e.message;
+        button.disabled = false;
+    }
+}
+
+// Nom, type et taille d'origine, affichés avant le téléchargement
+async function secureDescribe(meta) {
+    const keyText = location.hash.slice(1);
+    if (!keyText || !secureAvailable()) return;
+    try {
+        const info = await secureDecryptMeta(await secureImportKey(keyText), meta);
+        document.getElementById('secureName').textContent = info.name;
+    } catch (_) {
+        document.getElementById('secureName').textContent = '🔑 Clé incorrecte pour ce partage';
+    }
+}
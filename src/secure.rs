@@ -0,0 +1,536 @@
+// Partages chiffrés de bout en bout : le navigateur chiffre le fichier avant l'envoi
+// (WebCrypto, voir secure.js) et la clé ne circule que dans le fragment du lien, que
+// les navigateurs n'envoient jamais. Le serveur ne stocke que le contenu chiffré et
+// des métadonnées chiffrées qu'il ne sait pas lire, à part des fichiers partagés.
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::Mutex,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_multipart::Multipart;
+use actix_web::{get, http::header, post, web, HttpRequest, HttpResponse, Result};
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use futures_util::TryStreamExt as _;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{error, info};
+
+use crate::{
+    access, audit, cache,
+    crypto::StagedWriter,
+    escape_html, format_file_size, format_remaining, insufficient_storage, meta::FileMeta, read_text_field,
+    retention::{format_duration, parse_ttl},
+    metrics, quota, ratelimit, staging_path, storage,
+    throttle::{Direction, Transfer},
+    write_failed, Config, STAGING_DIR,
+};
+
+const SECURE_DIR: &str = ".secure";
+// Nom inscrit au journal d'audit : le vrai nom est chiffré
+const SHARE_NAME: &str = "(partage chiffré)";
+// Métadonnées chiffrées (nom, type, taille) encodées en base64url
+const MAX_META_LEN: usize = 4096;
+// Découpage utilisé par secure.js : blocs de 64 Ko, chacun suivi de son tag
+const CHUNK_LEN: u64 = 64 * 1024;
+const TAG_LEN: u64 = 16;
+
+const SCRIPT: &str = include_str!("secure.js");
+
+// Compteurs de téléchargement modifiés par plusieurs requêtes à la fois
+static DOWNLOADS: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone)]
+pub struct SecureConfig {
+    pub enabled: bool,
+    // Durée de vie par défaut et maximale d'un partage
+    default_ttl: u64,
+    max_ttl: u64,
+    // Nombre de téléchargements maximal qu'un partage peut autoriser
+    max_downloads: u32,
+}
+
+impl SecureConfig {
+    pub fn from_env() -> Self {
+        let max_ttl = parse_ttl(&env::var("SECURE_MAX_TTL").unwrap_or_default()).unwrap_or(7 * 86400);
+        Self {
+            enabled: env::var("SECURE_SHARES")
+                .map(|v| !matches!(v.to_lowercase().as_str(), "0" | "false" | "no" | "off"))
+                .unwrap_or(true),
+            default_ttl: parse_ttl(&env::var("SECURE_TTL").unwrap_or_default())
+                .unwrap_or(86400)
+                .min(max_ttl),
+            max_ttl,
+            max_downloads: env::var("SECURE_MAX_DOWNLOADS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n| n > 0)
+                .unwrap_or(100),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled {
+            return "désactivés".to_string();
+        }
+        format!(
+            "activés ({} par défaut, max {}, {} téléchargements max)",
+            format_duration(self.default_ttl),
+            format_duration(self.max_ttl),
+            self.max_downloads
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SecureShare {
+    id: String,
+    // Taille du contenu chiffré
+    size: u64,
+    // Nom, type et taille d'origine, chiffrés par le navigateur
+    meta: String,
+    created_at: u64,
+    expires_at: u64,
+    max_downloads: u32,
+    downloads: u32,
+    // Empreinte du jeton remis à l'expéditeur pour supprimer le partage
+    owner_token: String,
+    uploader: String,
+}
+
+impl SecureShare {
+    fn available(&self) -> bool {
+        self.expires_at > now() && self.downloads < self.max_downloads
+    }
+
+    // Taille du fichier d'origine, déduite du découpage en blocs
+    fn plain_size(&self) -> u64 {
+        let chunks = self.size.div_ceil(CHUNK_LEN + TAG_LEN).max(1);
+        self.size.saturating_sub(chunks * TAG_LEN)
+    }
+}
+
+fn secure_dir(upload_dir: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(SECURE_DIR)
+}
+
+fn share_path(upload_dir: &str, id: &str) -> PathBuf {
+    secure_dir(upload_dir).join(format!("{}.json", id))
+}
+
+fn blob_key(id: &str) -> String {
+    format!("{}/{}", SECURE_DIR, id)
+}
+
+// Identifiants aléatoires : un partage ne doit pas pouvoir être deviné et épuisé
+fn random_hex() -> String {
+    let mut bytes = [0u8; 16];
+    OsRng.fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+fn valid_id(id: &str) -> bool {
+    id.len() == 32 && id.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn token_hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+fn load(upload_dir: &str, id: &str) -> Option<SecureShare> {
+    fs::read_to_string(share_path(upload_dir, id))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+fn save(upload_dir: &str, share: &SecureShare) -> std::io::Result<()> {
+    let content = serde_json::to_string(share).map_err(std::io::Error::other)?;
+    fs::write(share_path(upload_dir, &share.id), content)?;
+    cache::invalidate_index();
+    Ok(())
+}
+
+fn list(upload_dir: &str) -> Vec<SecureShare> {
+    fs::read_dir(secure_dir(upload_dir))
+        .map(|dir| {
+            dir.flatten()
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|e| fs::read_to_string(e.path()).ok())
+                .filter_map(|content| serde_json::from_str(&content).ok())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn active_count(upload_dir: &str) -> usize {
+    list(upload_dir).iter().filter(|s| s.available()).count()
+}
+
+fn remove(upload_dir: &str, id: &str) -> std::io::Result<()> {
+    storage::open(upload_dir).delete(&blob_key(id))?;
+    fs::remove_file(share_path(upload_dir, id))?;
+    cache::invalidate_index();
+    quota::released();
+    Ok(())
+}
+
+// Appelé par le nettoyeur : partages expirés ou dont tous les téléchargements ont eu lieu
+pub fn purge_finished(upload_dir: &str) {
+    for share in list(upload_dir).into_iter().filter(|s| !s.available()) {
+        match remove(upload_dir, &share.id) {
+            Ok(_) => info!(share = %share.id, "🔒 Partage chiffré terminé, supprimé"),
+            Err(e) => error!(share = %share.id, error = %e, "❌ Erreur suppression partage chiffré"),
+        }
+    }
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Ce partage n'existe pas ou a expiré"
+    }))
+}
+
+// Partage encore disponible, lu hors du thread de l'exécuteur
+async fn find(upload_dir: &str, id: String) -> Result<Option<SecureShare>> {
+    if !valid_id(&id) {
+        return Ok(None);
+    }
+    let upload_dir = upload_dir.to_string();
+    let share = web::block(move || load(&upload_dir, &id)).await?;
+    Ok(share.filter(SecureShare::available))
+}
+
+#[get("/secure.js")]
+async fn script() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/javascript; charset=utf-8")
+        .body(SCRIPT)
+}
+
+// Champs attendus, dans l'ordre : meta, ttl, downloads puis le contenu chiffré (blob)
+#[post("/secure")]
+async fn upload(req: HttpRequest, mut payload: Multipart) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    if !config.secure.enabled {
+        return Ok(not_found());
+    }
+    fs::create_dir_all(PathBuf::from(&config.upload_dir).join(STAGING_DIR))?;
+    fs::create_dir_all(secure_dir(&config.upload_dir))?;
+
+    let quota = config.quota.clone();
+    let upload_dir = config.upload_dir.clone();
+    let budget = web::block(move || quota.remaining(&upload_dir)).await?;
+    // Taille maximale du contenu chiffré pour un fichier de taille maximale
+    let max_size = config.max_file_size as u64 + (config.max_file_size as u64 / CHUNK_LEN + 1) * TAG_LEN;
+    let limit = max_size.min(budget);
+    let too_large = || {
+        if budget < max_size {
+            return insufficient_storage();
+        }
+        HttpResponse::PayloadTooLarge().json(serde_json::json!({
+            "error": format!("Fichier trop volumineux (max: {} MB)", config.max_file_size / (1024 * 1024))
+        }))
+    };
+    let content_length = req
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if content_length.is_some_and(|len| len > limit + MAX_META_LEN as u64 + 4096) {
+        return Ok(too_large());
+    }
+
+    let started = Instant::now();
+    let transfer = match Transfer::start(&req, Direction::Upload) {
+        Ok(transfer) => transfer,
+        Err(response) => return Ok(response),
+    };
+
+    let mut meta = String::new();
+    let mut ttl = None;
+    let mut downloads = None;
+    let mut stored = None;
+    while let Some(mut field) = payload.try_next().await? {
+        match field.content_disposition().get_name() {
+            Some("meta") => meta = read_text_field(&mut field, MAX_META_LEN).await?,
+            Some("ttl") => ttl = Some(read_text_field(&mut field, 256).await?),
+            Some("downloads") => downloads = read_text_field(&mut field, 16).await?.parse::<u32>().ok(),
+            Some("blob") if stored.is_none() => {
+                let staged_path = staging_path(&config.upload_dir);
+                let cleanup_path = staged_path.clone();
+                let mut f = web::block(move || StagedWriter::create(&staged_path)).await??;
+                let mut size = 0u64;
+                while let Some(chunk) = field.try_next().await? {
+                    size += chunk.len() as u64;
+                    if size > limit {
+                        drop(f);
+                        let _ = fs::remove_file(&cleanup_path);
+                        return Ok(too_large());
+                    }
+                    transfer.pace(chunk.len()).await;
+                    f = match web::block(move || f.write_all(&chunk).map(|_| f)).await? {
+                        Ok(f) => f,
+                        Err(e) => return write_failed(&cleanup_path, "partage chiffré", e),
+                    };
+                }
+                if let Err(e) = web::block(move || f.finish()).await? {
+                    return write_failed(&cleanup_path, "partage chiffré", e);
+                }
+                stored = Some((cleanup_path, size));
+            }
+            _ => {}
+        }
+    }
+
+    let Some((staged, size)) = stored else {
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Contenu chiffré manquant"
+        })));
+    };
+    // Le serveur ne peut pas lire les métadonnées, il vérifie seulement leur forme
+    if meta.is_empty() || !meta.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        let _ = fs::remove_file(&staged);
+        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
+            "error": "Métadonnées chiffrées manquantes ou invalides"
+        })));
+    }
+
+    let ttl = ttl
+        .as_deref()
+        .and_then(parse_ttl)
+        .unwrap_or(config.secure.default_ttl)
+        .min(config.secure.max_ttl);
+    let owner_token = random_hex();
+    let share = SecureShare {
+        id: random_hex(),
+        size,
+        meta,
+        created_at: now(),
+        expires_at: now() + ttl,
+        max_downloads: downloads.unwrap_or(1).clamp(1, config.secure.max_downloads),
+        downloads: 0,
+        owner_token: token_hash(&owner_token),
+        uploader: access::client_address(&req),
+    };
+
+    let upload_dir = config.upload_dir.clone();
+    let saved = share.clone();
+    let published = web::block(move || {
+        storage::open(&upload_dir).put_file(&blob_key(&saved.id), &staged)?;
+        quota::stored(saved.size);
+        save(&upload_dir, &saved)
+    })
+    .await?;
+    if let Err(e) = published {
+        error!(error = %e, "❌ Erreur enregistrement partage chiffré");
+        let _ = remove(&config.upload_dir, &share.id);
+        return Err(e.into());
+    }
+
+    info!(share = %share.id, size = share.size, max_downloads = share.max_downloads, duration_ms = started.elapsed().as_millis() as u64, "🔒 Partage chiffré créé");
+    metrics::transfer_completed(Direction::Upload, share.size, started.elapsed());
+    audit::record(&config.upload_dir, audit::Entry::new(audit::Event::Share, &share.uploader, SHARE_NAME)
+        .id(&share.id)
+        .size(share.size)
+        .detail(&format!("{} téléchargement(s)", share.max_downloads)));
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": share.id,
+        "owner_token": owner_token,
+        "expires_at": share.expires_at,
+        "max_downloads": share.max_downloads,
+    })))
+}
+
+#[get("/secure/{id}")]
+async fn info(path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let Some(share) = find(&config.upload_dir, path.into_inner()).await? else {
+        return Ok(not_found());
+    };
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": share.id,
+        "size": share.size,
+        "meta": share.meta,
+        "expires_at": share.expires_at,
+        "downloads_left": share.max_downloads - share.downloads,
+    })))
+}
+
+// Toute réponse qui envoie du contenu (200 ou 206) compte comme un téléchargement :
+// sinon des plages répétées livreraient le contenu sans consommer la limite
+#[get("/secure/{id}/blob")]
+async fn blob(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    if find(&config.upload_dir, id.clone()).await?.is_none() {
+        return Ok(not_found());
+    }
+    let mut response = storage::download(&req, &config.upload_dir, &blob_key(&id), &format!("{}.bin", id), &FileMeta::default()).await?;
+    response.headers_mut().insert(header::CACHE_CONTROL, header::HeaderValue::from_static("no-store"));
+    if !response.status().is_success() {
+        return Ok(response);
+    }
+
+    // Compté avant l'envoi du contenu, sous verrou : deux téléchargements
+    // simultanés ne peuvent pas dépasser la limite
+    let upload_dir = config.upload_dir.clone();
+    let share_id = id.clone();
+    let counted = web::block(move || {
+        let _guard = DOWNLOADS.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(mut share) = load(&upload_dir, &share_id).filter(SecureShare::available) else {
+            return Ok(None);
+        };
+        share.downloads += 1;
+        save(&upload_dir, &share).map(|_| Some(share))
+    })
+    .await??;
+    let Some(share) = counted else {
+        return Ok(not_found());
+    };
+
+    info!(share = %share.id, downloads = share.downloads, max_downloads = share.max_downloads, "🔒 Partage chiffré téléchargé");
+    audit::download(&req, &config.upload_dir, &id, SHARE_NAME, &response);
+    Ok(response)
+}
+
+#[post("/secure/{id}/delete")]
+async fn delete(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    if !valid_id(&id) {
+        return Ok(not_found());
+    }
+    let Some(share) = load(&config.upload_dir, &id) else {
+        return Ok(not_found());
+    };
+    let token = req
+        .headers()
+        .get("X-Owner-Token")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if token_hash(token) != share.owner_token {
+        ratelimit::auth_failure(&req);
+        audit::record(&config.upload_dir, audit::Entry::new(audit::Event::Delete, &access::client_address(&req), SHARE_NAME)
+            .id(&share.id)
+            .failed("jeton invalide"));
+        return Ok(HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Jeton de suppression invalide"
+        })));
+    }
+
+    let upload_dir = config.upload_dir.clone();
+    let result = web::block(move || remove(&upload_dir, &id)).await?;
+    let entry = audit::Entry::new(audit::Event::Delete, &access::client_address(&req), SHARE_NAME).id(&share.id);
+    audit::record(&config.upload_dir, if result.is_ok() { entry } else { entry.failed("erreur") });
+    match result {
+        Ok(_) => {
+            info!(share = %share.id, "🔒 Partage chiffré supprimé par son expéditeur");
+            Ok(HttpResponse::Ok().json(serde_json::json!({ "deleted": share.id })))
+        }
+        Err(e) => {
+            error!(share = %share.id, error = %e, "❌ Erreur suppression partage chiffré");
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la suppression"
+            })))
+        }
+    }
+}
+
+// Page de réception : la clé reste dans le navigateur, qui déchiffre lui-même
+#[get("/s/{id}")]
+async fn page(path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    let body = match find(&config.upload_dir, id).await? {
+        Some(share) => format!(r#"
+                <p id="secureName">🔒 Fichier chiffré</p>
+                <p class="info">📏 {} | ⏳ {} | ⬇️ {} téléchargement(s) restant(s)</p>
+                <button class="btn" id="secureButton" onclick="secureDownload('{}')">🔓 Déchiffrer et télécharger</button>
+                <p id="secureStatus"></p>
+                <script>secureDescribe('{}');</script>
+        "#, format_file_size(share.plain_size()), format_remaining(share.expires_at), share.max_downloads - share.downloads, share.id, escape_html(&share.meta)),
+        None => r#"
+                <p>❌ Ce partage n'existe pas, a expiré ou a atteint son nombre de téléchargements.</p>
+        "#.to_string(),
+    };
+
+    let html = format!(r#"
+    <!DOCTYPE html>
+    <html lang="fr">
+    <head>
+        <meta charset="UTF-8">
+        <meta name="viewport" content="width=device-width, initial-scale=1.0">
+        <meta name="referrer" content="no-referrer">
+        <title>Serveur de partage - Fichier chiffré</title>
+        <style>
+            body {{
+                font-family: 'Segoe UI', Tahoma, Geneva, Verdana, sans-serif;
+                background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+                color: white;
+                min-height: 100vh;
+                margin: 0;
+                padding: 20px;
+                box-sizing: border-box;
+            }}
+
+            .container {{
+                max-width: 600px;
+                margin: 60px auto;
+                background: rgba(255, 255, 255, 0.1);
+                border-radius: 20px;
+                padding: 30px;
+                text-align: center;
+                box-shadow: 0 8px 32px rgba(0, 0, 0, 0.3);
+            }}
+
+            #secureName {{
+                font-size: 1.4em;
+                font-weight: bold;
+                word-break: break-all;
+            }}
+
+            .info {{
+                opacity: 0.8;
+            }}
+
+            .btn {{
+                background: linear-gradient(45deg, #4ecdc4, #44a08d);
+                color: white;
+                border: none;
+                padding: 12px 24px;
+                border-radius: 25px;
+                font-weight: bold;
+                cursor: pointer;
+                margin: 15px 0;
+            }}
+
+            .btn:disabled {{
+                opacity: 0.5;
+            }}
+        </style>
+        <script src="/secure.js"></script>
+    </head>
+    <body>
+        <div class="container">
+            <h1>🔒 Fichier chiffré</h1>
+            <p class="info">Le fichier est déchiffré dans votre navigateur, le serveur n'a jamais accès à son contenu.</p>
+            {}
+        </div>
+    </body>
+    </html>
+    "#, body);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header(("Referrer-Policy", "no-referrer"))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .body(html))
+}
@@ -0,0 +1,144 @@
+// Arrêt propre sur SIGINT/SIGTERM : plus de nouvelles connexions, les
+// transferts en cours ont un délai pour finir, les autres sont interrompus
+use std::{
+    env, fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::Instant,
+};
+
+use actix_web::dev::ServerHandle;
+use futures_util::future::{self, Either};
+use tracing::{info, warn};
+
+use crate::{
+    metrics::{self, TransferCounts},
+    retention::{format_duration, parse_ttl},
+    scan,
+    throttle::Direction,
+    webhook, STAGING_DIR,
+};
+
+const DEFAULT_TIMEOUT: u64 = 30;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static SNAPSHOT: OnceLock<Snapshot> = OnceLock::new();
+
+// Vrai dès qu'un signal d'arrêt a été reçu
+pub fn in_progress() -> bool {
+    SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+pub struct ShutdownConfig {
+    // Délai laissé aux transferts en cours, en secondes (0 = interruption immédiate)
+    pub timeout: u64,
+}
+
+impl ShutdownConfig {
+    pub fn from_env() -> Self {
+        Self {
+            timeout: env::var("SHUTDOWN_TIMEOUT")
+                .ok()
+                .and_then(|v| if v.trim() == "0" { Some(0) } else { parse_ttl(&v) })
+                .unwrap_or(DEFAULT_TIMEOUT),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match self.timeout {
+            0 => "immédiat".to_string(),
+            timeout => format!("{} pour finir les transferts", format_duration(timeout)),
+        }
+    }
+}
+
+// État des transferts au moment du signal, pour le bilan de fin
+struct Snapshot {
+    at: Instant,
+    uploads: TransferCounts,
+    downloads: TransferCounts,
+}
+
+impl Snapshot {
+    fn take() -> Self {
+        Self {
+            at: Instant::now(),
+            uploads: metrics::transfer_counts(Direction::Upload),
+            downloads: metrics::transfer_counts(Direction::Download),
+        }
+    }
+}
+
+async fn signal() {
+    #[cfg(unix)]
+    {
+        use actix_web::rt::signal::unix::{signal, SignalKind};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            let interrupt = actix_web::rt::signal::ctrl_c();
+            futures_util::pin_mut!(interrupt);
+            let terminate = terminate.recv();
+            futures_util::pin_mut!(terminate);
+            future::select(interrupt, terminate).await;
+            return;
+        }
+    }
+    let _ = actix_web::rt::signal::ctrl_c().await;
+}
+
+// Attend le signal d'arrêt puis lance l'arrêt progressif du serveur ;
+// un second signal pendant l'attente quitte immédiatement
+pub fn watch(handle: ServerHandle, upload_dir: String) {
+    actix_web::rt::spawn(async move {
+        signal().await;
+        SHUTTING_DOWN.store(true, Ordering::Relaxed);
+        let snapshot = SNAPSHOT.get_or_init(Snapshot::take);
+        info!(
+            uploads = snapshot.uploads.active,
+            downloads = snapshot.downloads.active,
+            "🛑 Arrêt demandé, fin des transferts en cours"
+        );
+
+        let stop = handle.stop(true);
+        futures_util::pin_mut!(stop);
+        let forced = signal();
+        futures_util::pin_mut!(forced);
+        if let Either::Right(_) = future::select(stop, forced).await {
+            warn!("⚠️ Second signal reçu, arrêt immédiat");
+            finish(&upload_dir);
+            std::process::exit(130);
+        }
+    });
+}
+
+// Après l'arrêt du serveur : supprime les uploads interrompus et résume l'arrêt
+pub fn finish(upload_dir: &str) {
+    let Some(snapshot) = SNAPSHOT.get() else {
+        return;
+    };
+    let staging = PathBuf::from(upload_dir).join(STAGING_DIR);
+    let partial = fs::read_dir(&staging).map(|entries| entries.count()).unwrap_or_default();
+    let _ = fs::remove_dir_all(&staging);
+
+    let drained = |before: &TransferCounts, direction: Direction| {
+        let after = metrics::transfer_counts(direction);
+        let completed = after.completed.saturating_sub(before.completed);
+        (completed, before.active.saturating_sub(completed))
+    };
+    let (uploads_completed, uploads_interrupted) = drained(&snapshot.uploads, Direction::Upload);
+    let (downloads_completed, downloads_interrupted) = drained(&snapshot.downloads, Direction::Download);
+    info!(
+        uploads_completed,
+        uploads_interrupted,
+        downloads_completed,
+        downloads_interrupted,
+        partial_files_removed = partial,
+        pending_scans = scan::pending_files(upload_dir).len(),
+        pending_webhooks = webhook::pending(),
+        duration_ms = snapshot.at.elapsed().as_millis() as u64,
+        "👋 Serveur arrêté"
+    );
+}
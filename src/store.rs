@@ -0,0 +1,221 @@
+// Index SQLite des métadonnées, reconstruit à partir du disque (fichiers partagés
+// et fichiers .meta/<id>.json) s'il est supprimé ou désynchronisé
+use std::{
+    collections::HashMap,
+    fs,
+    io::Read,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::{
+    meta::{self, FileMeta},
+    names,
+    policy::{FileKind, SNIFF_LEN},
+    storage::{self, ObjectInfo, Storage},
+};
+
+const DB_FILE: &str = "index.db";
+
+// Colonnes ajoutées après la création de l'index, ajoutées aux bases existantes
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("blake3", "TEXT"),
+    ("verified_at", "INTEGER"),
+    ("corrupt", "INTEGER NOT NULL DEFAULT 0"),
+];
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS files (
+        id TEXT PRIMARY KEY,
+        name TEXT,
+        expires_at INTEGER,
+        uploaded_at INTEGER,
+        uploader TEXT,
+        sha256 TEXT,
+        mime TEXT,
+        description TEXT,
+        tags TEXT NOT NULL DEFAULT '[]',
+        blake3 TEXT,
+        verified_at INTEGER,
+        corrupt INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX IF NOT EXISTS files_name ON files (name);
+    CREATE INDEX IF NOT EXISTS files_sha256 ON files (sha256);
+";
+
+fn db_path(upload_dir: &str) -> PathBuf {
+    meta::meta_dir(upload_dir).join(DB_FILE)
+}
+
+fn open(upload_dir: &str) -> rusqlite::Result<Connection> {
+    let _ = fs::create_dir_all(meta::meta_dir(upload_dir));
+    let conn = Connection::open(db_path(upload_dir))?;
+    // Plusieurs requêtes peuvent écrire en même temps
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.execute_batch(SCHEMA)?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('files')")?;
+    let existing: Vec<String> = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    for (column, definition) in ADDED_COLUMNS {
+        if !existing.iter().any(|name| name == column) {
+            conn.execute_batch(&format!("ALTER TABLE files ADD COLUMN {} {}", column, definition))?;
+        }
+    }
+    Ok(())
+}
+
+fn from_row(row: &Row) -> rusqlite::Result<(String, FileMeta)> {
+    let tags: String = row.get("tags")?;
+    Ok((
+        row.get("id")?,
+        FileMeta {
+            name: row.get("name")?,
+            expires_at: row.get("expires_at")?,
+            uploaded_at: row.get("uploaded_at")?,
+            uploader: row.get("uploader")?,
+            sha256: row.get("sha256")?,
+            blake3: row.get("blake3")?,
+            verified_at: row.get("verified_at")?,
+            corrupt: row.get("corrupt")?,
+            mime: row.get("mime")?,
+            description: row.get("description")?,
+            tags: serde_json::from_str(&tags).unwrap_or_default(),
+        },
+    ))
+}
+
+pub fn get(upload_dir: &str, id: &str) -> rusqlite::Result<Option<FileMeta>> {
+    open(upload_dir)?
+        .query_row("SELECT * FROM files WHERE id = ?1", [id], from_row)
+        .optional()
+        .map(|row| row.map(|(_, meta)| meta))
+}
+
+// Métadonnées de tous les fichiers indexés, par identifiant
+pub fn all(upload_dir: &str) -> rusqlite::Result<HashMap<String, FileMeta>> {
+    let conn = open(upload_dir)?;
+    let mut stmt = conn.prepare("SELECT * FROM files")?;
+    let rows = stmt.query_map([], from_row)?;
+    rows.collect()
+}
+
+// Identifiant du fichier portant ce nom d'affichage (un fichier sans nom
+// enregistré s'affiche sous son identifiant), le plus récent s'il y en a plusieurs
+pub fn find_by_name(upload_dir: &str, name: &str) -> rusqlite::Result<Option<String>> {
+    open(upload_dir)?
+        .query_row(
+            "SELECT id FROM files WHERE name = ?1 OR (name IS NULL AND id = ?1)
+             ORDER BY uploaded_at DESC LIMIT 1",
+            [name],
+            |row| row.get(0),
+        )
+        .optional()
+}
+
+// Fichiers indexés ayant ce contenu
+pub fn find_by_sha256(upload_dir: &str, sha256: &str) -> rusqlite::Result<Vec<(String, FileMeta)>> {
+    let conn = open(upload_dir)?;
+    let mut stmt = conn.prepare("SELECT * FROM files WHERE sha256 = ?1 ORDER BY uploaded_at")?;
+    let rows = stmt.query_map([sha256], from_row)?;
+    rows.collect()
+}
+
+pub fn upsert(upload_dir: &str, id: &str, meta: &FileMeta) -> rusqlite::Result<()> {
+    open(upload_dir)?.execute(
+        "INSERT OR REPLACE INTO files
+            (id, name, expires_at, uploaded_at, uploader, sha256, mime, description, tags, blake3, verified_at, corrupt)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+        params![
+            id,
+            meta.name,
+            meta.expires_at,
+            meta.uploaded_at,
+            meta.uploader,
+            meta.sha256,
+            meta.mime,
+            meta.description,
+            serde_json::to_string(&meta.tags).unwrap_or_else(|_| "[]".to_string()),
+            meta.blake3,
+            meta.verified_at,
+            meta.corrupt,
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn delete(upload_dir: &str, id: &str) -> rusqlite::Result<()> {
+    open(upload_dir)?.execute("DELETE FROM files WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+#[derive(Debug, Default)]
+pub struct SyncReport {
+    pub indexed: usize,
+    pub added: usize,
+    pub removed: usize,
+}
+
+// Remet l'index en accord avec le dossier partagé : les fichiers inconnus sont
+// indexés (métadonnées reprises du fichier .json, sinon recalculées depuis le
+// contenu), les entrées des fichiers disparus sont supprimées
+pub fn sync(upload_dir: &str) -> std::io::Result<SyncReport> {
+    let mut indexed = all(upload_dir).map_err(std::io::Error::other)?;
+    let mut report = SyncReport::default();
+
+    let storage = storage::open(upload_dir);
+    let stored = storage.list("")?;
+
+    for object in stored.iter().filter(|o| names::valid_id(&o.key)) {
+        let id = &object.key;
+        if indexed.remove(id).is_some() {
+            report.indexed += 1;
+            continue;
+        }
+        let file_meta = rebuild_meta(upload_dir, storage.as_ref(), object);
+        meta::write_sidecar(upload_dir, id, &file_meta)?;
+        upsert(upload_dir, id, &file_meta).map_err(std::io::Error::other)?;
+        report.added += 1;
+    }
+
+    // Ce qui reste dans l'index n'existe plus dans le stockage
+    for id in indexed.keys() {
+        meta::remove(upload_dir, id);
+        report.removed += 1;
+    }
+    report.indexed += report.added;
+    Ok(report)
+}
+
+// Métadonnées d'un fichier absent de l'index : le fichier .json s'il existe,
+// complété par ce que le contenu permet de retrouver
+fn rebuild_meta(upload_dir: &str, storage: &dyn Storage, object: &ObjectInfo) -> FileMeta {
+    let id = object.key.as_str();
+    let mut file_meta = meta::read_sidecar(upload_dir, id);
+
+    if file_meta.uploaded_at.is_none() {
+        file_meta.uploaded_at = Some(object.modified)
+            .filter(|&modified| modified > 0)
+            .or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs()));
+    }
+    if file_meta.sha256.is_none() {
+        file_meta.sha256 = storage::sha256(storage, id).ok();
+    }
+    if file_meta.mime.is_none() {
+        let mut head = Vec::with_capacity(SNIFF_LEN);
+        let _ = storage
+            .get(id, Some(0..(SNIFF_LEN as u64).min(object.size)))
+            .and_then(|reader| reader.take(SNIFF_LEN as u64).read_to_end(&mut head));
+        let read = head.len();
+        let name = file_meta.name.clone().unwrap_or_else(|| id.to_string());
+        let kind = FileKind::from_content(&head[..read]).unwrap_or_else(|| FileKind::from_name(&name));
+        file_meta.mime = Some(kind.mime);
+    }
+    file_meta
+}
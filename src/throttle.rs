@@ -0,0 +1,304 @@
+// Limitation de débit des transferts : seaux à jetons global et par client,
+// nombre de transferts simultanés par client, adresses de confiance exemptées
+use std::{
+    collections::HashMap,
+    env,
+    net::IpAddr,
+    sync::{LazyLock, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{http::header, HttpRequest, HttpResponse};
+use ipnet::IpNet;
+use tracing::warn;
+
+use crate::{access, format_file_size, metrics, shutdown};
+
+// Fréquence du nettoyage des clients sans transfert dont les seaux sont pleins
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Upload,
+    Download,
+}
+
+#[derive(Debug, Clone)]
+pub struct ThrottleConfig {
+    // Débits maximaux en octets/s, tous clients confondus (None = illimité)
+    upload: Option<u64>,
+    download: Option<u64>,
+    // Débits maximaux en octets/s pour chaque client
+    client_upload: Option<u64>,
+    client_download: Option<u64>,
+    // Transferts simultanés autorisés par client
+    max_transfers: Option<usize>,
+    // Adresses exemptées de toutes les limites
+    trusted: Vec<IpNet>,
+}
+
+impl ThrottleConfig {
+    pub fn from_env() -> Self {
+        Self {
+            upload: rate("UPLOAD_LIMIT"),
+            download: rate("DOWNLOAD_LIMIT"),
+            client_upload: rate("CLIENT_UPLOAD_LIMIT"),
+            client_download: rate("CLIENT_DOWNLOAD_LIMIT"),
+            max_transfers: env::var("MAX_CLIENT_TRANSFERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|&n| n > 0),
+            trusted: env::var("TRUSTED_IPS")
+                .map(|v| access::parse_networks(&v))
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        let speed = |rate: Option<u64>| rate.map_or("∞".to_string(), |rate| format!("{}/s", format_file_size(rate)));
+        let mut rules = Vec::new();
+        if self.upload.is_some() || self.download.is_some() {
+            rules.push(format!("↑ {} ↓ {}", speed(self.upload), speed(self.download)));
+        }
+        if self.client_upload.is_some() || self.client_download.is_some() {
+            rules.push(format!("par client ↑ {} ↓ {}", speed(self.client_upload), speed(self.client_download)));
+        }
+        if let Some(max) = self.max_transfers {
+            rules.push(format!("{} transfert(s) simultané(s)/client", max));
+        }
+        if rules.is_empty() {
+            return "aucune".to_string();
+        }
+        if !self.trusted.is_empty() {
+            rules.push(format!("{} réseau(x) de confiance", self.trusted.len()));
+        }
+        rules.join(", ")
+    }
+
+    pub fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.iter().any(|net| net.contains(&ip))
+    }
+
+    fn limits(&self, direction: Direction) -> (Option<u64>, Option<u64>) {
+        match direction {
+            Direction::Upload => (self.upload, self.client_upload),
+            Direction::Download => (self.download, self.client_download),
+        }
+    }
+}
+
+// Débit en Ko/s dans l'environnement, converti en octets/s
+fn rate(var: &str) -> Option<u64> {
+    env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse::<u64>().ok())
+        .filter(|&kb| kb > 0)
+        .map(|kb| kb * 1024)
+}
+
+// Seau à jetons « à crédit » : un bloc est toujours accepté, l'attente
+// qui suit ramène le débit moyen sous la limite. Une seconde de rafale au plus.
+struct Bucket {
+    tokens: f64,
+    last: Instant,
+}
+
+impl Bucket {
+    fn new(rate: u64) -> Self {
+        Self {
+            tokens: rate as f64,
+            last: Instant::now(),
+        }
+    }
+
+    fn refill(&self, rate: f64, now: Instant) -> f64 {
+        (self.tokens + now.duration_since(self.last).as_secs_f64() * rate).min(rate)
+    }
+
+    fn take(&mut self, rate: u64, bytes: usize) -> Duration {
+        let now = Instant::now();
+        let rate = rate as f64;
+        self.tokens = self.refill(rate, now);
+        self.last = now;
+        self.tokens -= bytes as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[derive(Default)]
+struct Buckets {
+    upload: Option<Bucket>,
+    download: Option<Bucket>,
+}
+
+impl Buckets {
+    fn take(&mut self, direction: Direction, rate: u64, bytes: usize) -> Duration {
+        let bucket = match direction {
+            Direction::Upload => &mut self.upload,
+            Direction::Download => &mut self.download,
+        };
+        bucket.get_or_insert_with(|| Bucket::new(rate)).take(rate, bytes)
+    }
+
+    // Seaux pleins : une nouvelle rafale est de toute façon permise
+    fn full(&self, config: &ThrottleConfig, now: Instant) -> bool {
+        let full = |bucket: &Option<Bucket>, rate: Option<u64>| match (bucket, rate) {
+            (Some(bucket), Some(rate)) => bucket.refill(rate as f64, now) >= rate as f64,
+            _ => true,
+        };
+        full(&self.upload, config.client_upload) && full(&self.download, config.client_download)
+    }
+}
+
+#[derive(Default)]
+struct Client {
+    transfers: usize,
+    buckets: Buckets,
+}
+
+#[derive(Default)]
+struct State {
+    global: Buckets,
+    clients: HashMap<IpAddr, Client>,
+    pruned_at: Option<Instant>,
+}
+
+impl State {
+    // Un client sans transfert garde ses seaux jusqu'à ce qu'ils soient pleins,
+    // sinon chaque requête successive repartirait avec une rafale complète.
+    // Parcours de toute la table : au plus une fois par PRUNE_INTERVAL.
+    fn prune(&mut self, config: &ThrottleConfig, now: Instant) {
+        if self.pruned_at.is_some_and(|at| now.duration_since(at) < PRUNE_INTERVAL) {
+            return;
+        }
+        self.pruned_at = Some(now);
+        self.clients.retain(|_, client| client.transfers > 0 || !client.buckets.full(config, now));
+    }
+}
+
+static STATE: LazyLock<Mutex<State>> = LazyLock::new(Mutex::default);
+
+// Transfert en cours : compté pour son client jusqu'à sa fin (Drop)
+pub struct Transfer {
+    config: ThrottleConfig,
+    direction: Direction,
+    // None pour une adresse de confiance ou inconnue
+    client: Option<IpAddr>,
+    exempt: bool,
+}
+
+impl Transfer {
+    // Refuse le transfert (429) si le client en a déjà trop en cours
+    pub fn start(req: &HttpRequest, direction: Direction) -> Result<Self, HttpResponse> {
+        // Arrêt en cours : seuls les transferts déjà commencés peuvent finir
+        if shutdown::in_progress() {
+            metrics::transfer_failed(direction, "arrêt du serveur", 0);
+            return Err(HttpResponse::ServiceUnavailable()
+                .insert_header((header::RETRY_AFTER, "30"))
+                .json(serde_json::json!({ "error": "Serveur en cours d'arrêt" })));
+        }
+        let config = ThrottleConfig::from_env();
+        let ip = access::client_ip(req);
+        let exempt = ip.is_some_and(|ip| config.is_trusted(ip));
+        let client = ip.filter(|_| !exempt);
+
+        if let Some(ip) = client {
+            let mut state = STATE.lock().unwrap();
+            state.prune(&config, Instant::now());
+            let entry = state.clients.entry(ip).or_default();
+            if config.max_transfers.is_some_and(|max| entry.transfers >= max) {
+                let max = config.max_transfers.unwrap_or_default();
+                if entry.transfers == 0 {
+                    state.clients.remove(&ip);
+                }
+                warn!(%ip, max, "🚦 Transfert refusé, trop de transferts en cours");
+                metrics::transfer_failed(direction, "trop de transferts", 0);
+                return Err(HttpResponse::TooManyRequests().json(serde_json::json!({
+                    "error": format!("Trop de transferts simultanés (max: {})", max)
+                })));
+            }
+            entry.transfers += 1;
+        }
+        metrics::transfer_started(direction);
+        Ok(Self { config, direction, client, exempt })
+    }
+
+    // Attend ce qu'il faut après l'envoi ou la réception de `bytes` octets
+    pub async fn pace(&self, bytes: usize) {
+        let delay = self.reserve(bytes);
+        if !delay.is_zero() {
+            actix_web::rt::time::sleep(delay).await;
+        }
+    }
+
+    fn reserve(&self, bytes: usize) -> Duration {
+        if self.exempt {
+            return Duration::ZERO;
+        }
+        let (global, per_client) = self.config.limits(self.direction);
+        let mut state = STATE.lock().unwrap();
+        let mut delay = Duration::ZERO;
+        if let Some(rate) = global {
+            delay = delay.max(state.global.take(self.direction, rate, bytes));
+        }
+        if let (Some(rate), Some(ip)) = (per_client, self.client) {
+            if let Some(client) = state.clients.get_mut(&ip) {
+                delay = delay.max(client.buckets.take(self.direction, rate, bytes));
+            }
+        }
+        delay
+    }
+}
+
+impl Drop for Transfer {
+    fn drop(&mut self) {
+        metrics::transfer_ended(self.direction);
+        let Some(ip) = self.client else {
+            return;
+        };
+        let mut state = STATE.lock().unwrap();
+        if let Some(client) = state.clients.get_mut(&ip) {
+            client.transfers = client.transfers.saturating_sub(1);
+            if client.transfers == 0 && client.buckets.full(&self.config, Instant::now()) {
+                state.clients.remove(&ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idle_clients_keep_their_buckets_until_refilled() {
+        let config = ThrottleConfig {
+            upload: None,
+            download: None,
+            client_upload: None,
+            client_download: Some(1000),
+            max_transfers: None,
+            trusted: Vec::new(),
+        };
+        let start = Instant::now();
+        let mut state = State::default();
+        let ip = IpAddr::from([10, 0, 0, 1]);
+        // Rafale consommée puis 500 octets à crédit : plein dans 1,5 s
+        let client = state.clients.entry(ip).or_default();
+        client.buckets.download = Some(Bucket { tokens: -500.0, last: start });
+        assert!(!client.buckets.full(&config, start + Duration::from_secs(1)));
+        assert!(client.buckets.full(&config, start + Duration::from_millis(1500)));
+
+        state.prune(&config, start + Duration::from_secs(1));
+        assert!(state.clients.contains_key(&ip));
+        // Pas de nouveau parcours avant PRUNE_INTERVAL
+        state.prune(&config, start + Duration::from_secs(2));
+        assert!(state.clients.contains_key(&ip));
+        state.prune(&config, start + PRUNE_INTERVAL + Duration::from_secs(1));
+        assert!(state.clients.is_empty());
+    }
+}
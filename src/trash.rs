@@ -0,0 +1,301 @@
+// Corbeille : les fichiers supprimés y sont conservés et peuvent être restaurés
+use std::{
+    env, fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{post, web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::{
+    access, audit, cache,
+    conflict::ConflictPolicy,
+    dedup,
+    meta::{self, FileMeta},
+    names, publish_upload, quota,
+    retention::parse_ttl,
+    storage, versions, Config,
+};
+
+const TRASH_DIR: &str = ".trash";
+
+#[derive(Debug, Clone)]
+pub struct TrashConfig {
+    // Âge au-delà duquel les éléments de la corbeille sont purgés (None = jamais)
+    max_age: Option<u64>,
+}
+
+impl TrashConfig {
+    pub fn from_env() -> Self {
+        Self {
+            max_age: parse_ttl(&env::var("TRASH_RETENTION").unwrap_or_else(|_| "30d".to_string())),
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        self.max_age
+            .map_or("illimitée".to_string(), crate::retention::format_duration)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub name: String,
+    // Identifiant de stockage du fichier avant suppression (historique des versions)
+    #[serde(default)]
+    pub file_id: Option<String>,
+    pub size: u64,
+    pub file_type: String,
+    pub deleted_at: u64,
+    pub deleted_by: String,
+    #[serde(default)]
+    pub meta: FileMeta,
+}
+
+fn trash_dir(upload_dir: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(TRASH_DIR)
+}
+
+fn entry_path(upload_dir: &str, id: &str) -> PathBuf {
+    trash_dir(upload_dir).join(format!("{}.json", id))
+}
+
+// Clé du contenu d'un élément dans le stockage
+fn blob_key(id: &str) -> String {
+    format!("{}/{}", TRASH_DIR, id)
+}
+
+// Les identifiants sont des timestamps en nanosecondes : refuser tout le reste
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+// Déplace un fichier partagé vers la corbeille au lieu de le supprimer
+pub fn move_to_trash(upload_dir: &str, file_id: &str, deleted_by: &str) -> std::io::Result<TrashEntry> {
+    let storage = storage::open(upload_dir);
+    let size = storage
+        .stat(file_id)?
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, file_id.to_string()))?
+        .size;
+    fs::create_dir_all(trash_dir(upload_dir))?;
+
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+        .to_string();
+    let file_meta = meta::load(upload_dir, file_id);
+    let name = file_meta.name.clone().unwrap_or_else(|| file_id.to_string());
+    let entry = TrashEntry {
+        id: id.clone(),
+        file_type: names::file_type(&name),
+        name,
+        file_id: Some(file_id.to_string()),
+        size,
+        deleted_at: now(),
+        deleted_by: deleted_by.to_string(),
+        meta: file_meta,
+    };
+
+    storage.rename(file_id, &blob_key(&id))?;
+    let content = serde_json::to_string(&entry).map_err(std::io::Error::other)?;
+    fs::write(entry_path(upload_dir, &id), content)?;
+    meta::remove(upload_dir, file_id);
+    Ok(entry)
+}
+
+pub fn list(upload_dir: &str) -> Vec<TrashEntry> {
+    let mut entries: Vec<TrashEntry> = fs::read_dir(trash_dir(upload_dir))
+        .map(|dir| {
+            dir.flatten()
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|e| fs::read_to_string(e.path()).ok())
+                .filter_map(|content| serde_json::from_str(&content).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    entries.sort_by_key(|e| std::cmp::Reverse(e.deleted_at));
+    entries
+}
+
+fn load_entry(upload_dir: &str, id: &str) -> Option<TrashEntry> {
+    fs::read_to_string(entry_path(upload_dir, id))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+}
+
+// Restaure un élément sous son nom d'origine, la stratégie de conflit
+// s'applique si ce nom a été repris entre-temps
+fn restore_entry(upload_dir: &str, entry: &TrashEntry, policy: ConflictPolicy, max_versions: usize, dedup: bool) -> std::io::Result<String> {
+    let mut file_meta = entry.meta.clone();
+    // Un fichier expiré pendant son passage en corbeille ne doit pas disparaître aussitôt
+    if file_meta.expires_at.is_some_and(|at| at <= now()) {
+        file_meta.expires_at = None;
+    }
+    // Le contenu repasse par le staging pour être publié comme un nouvel upload
+    let storage = storage::open(upload_dir);
+    let staged = PathBuf::from(upload_dir)
+        .join(crate::STAGING_DIR)
+        .join(format!("trash_{}", entry.id));
+    fs::create_dir_all(staged.parent().unwrap())?;
+    storage.get_file(&blob_key(&entry.id), &staged)?;
+    let restored = match publish_upload(upload_dir, &staged, &entry.name, &file_meta, policy, max_versions, dedup) {
+        Ok(restored) => restored,
+        Err(e) => {
+            // L'élément reste dans la corbeille
+            storage.put_file(&blob_key(&entry.id), &staged)?;
+            return Err(e);
+        }
+    };
+    // Le fichier restauré a un nouvel identifiant : il reprend son historique
+    // (les éléments plus anciens étaient stockés sous leur nom)
+    if !restored.duplicate {
+        let file_id = entry.file_id.as_deref().unwrap_or(&entry.name);
+        versions::move_history(upload_dir, file_id, &restored.id);
+    }
+    fs::remove_file(entry_path(upload_dir, &entry.id))?;
+    cache::invalidate_index();
+    // Déjà compté dans la corbeille : la publication l'a ajouté une seconde fois
+    quota::released();
+    Ok(restored.stored_as)
+}
+
+fn purge_entry(upload_dir: &str, entry: &TrashEntry) -> std::io::Result<()> {
+    storage::open(upload_dir).delete(&blob_key(&entry.id))?;
+    dedup::release(upload_dir, entry.meta.sha256.as_deref());
+    fs::remove_file(entry_path(upload_dir, &entry.id))?;
+    cache::invalidate_index();
+    quota::released();
+    Ok(())
+}
+
+// Vidage automatique des éléments trop anciens (appelé par le nettoyeur)
+pub fn purge_expired(upload_dir: &str, trash: &TrashConfig) {
+    let Some(max_age) = trash.max_age else {
+        return;
+    };
+    let limit = now().saturating_sub(max_age);
+    for entry in list(upload_dir).into_iter().filter(|e| e.deleted_at <= limit) {
+        match purge_entry(upload_dir, &entry) {
+            Ok(_) => {
+                info!(file = %entry.name, size = entry.size, "♻️ Corbeille: fichier expiré supprimé définitivement");
+                audit::record(upload_dir, audit::Entry::new(audit::Event::Delete, audit::SERVER, &entry.name)
+                    .size(entry.size)
+                    .detail("purge automatique de la corbeille"));
+            }
+            Err(e) => error!(file = %entry.name, error = %e, "❌ Erreur purge corbeille"),
+        }
+    }
+}
+
+fn audit_purge(upload_dir: &str, req: &HttpRequest, entry: &TrashEntry) {
+    audit::record(upload_dir, audit::Entry::new(audit::Event::Delete, &access::client_address(req), &entry.name)
+        .size(entry.size)
+        .detail("suppression définitive"));
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Élément introuvable dans la corbeille"
+    }))
+}
+
+#[post("/trash/{id}/restore")]
+async fn restore(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    if !valid_id(&id) {
+        return Ok(not_found());
+    }
+    let Some(entry) = load_entry(&config.upload_dir, &id) else {
+        return Ok(not_found());
+    };
+
+    let upload_dir = config.upload_dir.clone();
+    let policy = config.conflict_policy;
+    let max_versions = config.versioning.max_versions;
+    let dedup = config.dedup.enabled;
+    let restoring = entry.clone();
+    let restored = web::block(move || restore_entry(&upload_dir, &restoring, policy, max_versions, dedup)).await?;
+    let audit_entry = audit::Entry::new(audit::Event::Restore, &access::client_address(&req), &entry.name)
+        .id(entry.file_id.as_deref().unwrap_or(&entry.id))
+        .size(entry.size);
+    match restored {
+        Ok(restored) => {
+            info!(file = %entry.name, restored_as = %restored, size = entry.size, "♻️ Fichier restauré");
+            let detail = if restored == entry.name { "corbeille".to_string() } else { format!("corbeille, restauré sous {}", restored) };
+            audit::record(&config.upload_dir, audit_entry.detail(&detail));
+            Ok(HttpResponse::Found()
+                .insert_header(("Location", "/"))
+                .finish())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            audit::record(&config.upload_dir, audit_entry.failed("conflit de nom"));
+            Ok(HttpResponse::Conflict().json(serde_json::json!({
+                "error": e.to_string()
+            })))
+        }
+        Err(e) => {
+            error!(file = %entry.name, error = %e, "❌ Erreur restauration fichier");
+            audit::record(&config.upload_dir, audit_entry.failed("erreur"));
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la restauration"
+            })))
+        }
+    }
+}
+
+#[post("/trash/{id}/purge")]
+async fn purge(req: HttpRequest, path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let id = path.into_inner();
+    if !valid_id(&id) {
+        return Ok(not_found());
+    }
+    let Some(entry) = load_entry(&config.upload_dir, &id) else {
+        return Ok(not_found());
+    };
+
+    match purge_entry(&config.upload_dir, &entry) {
+        Ok(_) => {
+            info!(file = %entry.name, size = entry.size, "🔥 Fichier supprimé définitivement");
+            audit_purge(&config.upload_dir, &req, &entry);
+            Ok(HttpResponse::Found()
+                .insert_header(("Location", "/"))
+                .finish())
+        }
+        Err(e) => {
+            error!(file = %entry.name, error = %e, "❌ Erreur purge corbeille");
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la suppression définitive"
+            })))
+        }
+    }
+}
+
+#[post("/trash/empty")]
+async fn empty(req: HttpRequest) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let entries = list(&config.upload_dir);
+    let count = entries.len();
+    for entry in entries {
+        match purge_entry(&config.upload_dir, &entry) {
+            Ok(_) => audit_purge(&config.upload_dir, &req, &entry),
+            Err(e) => error!(file = %entry.name, error = %e, "❌ Erreur purge corbeille"),
+        }
+    }
+    info!(count, "🔥 Corbeille vidée");
+    Ok(HttpResponse::Found()
+        .insert_header(("Location", "/"))
+        .finish())
+}
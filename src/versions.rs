@@ -0,0 +1,268 @@
+// Historique des versions : un upload portant le même nom remplace le fichier
+// courant, l'ancien contenu est archivé dans .versions/<identifiant>/
+use std::{
+    env, fs,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{get, http::{header, StatusCode}, post, web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, warn};
+
+use crate::{
+    access, audit, cache, dedup,
+    meta::{self, FileMeta},
+    names, quota, storage, Config,
+};
+
+const VERSIONS_DIR: &str = ".versions";
+
+// Le versionnage est activé par la stratégie de conflit "version"
+// (CONFLICT_POLICY=version ou VERSIONING=true)
+#[derive(Debug, Clone)]
+pub struct VersioningConfig {
+    // Nombre de versions précédentes conservées par fichier
+    pub max_versions: usize,
+}
+
+impl VersioningConfig {
+    pub fn from_env() -> Self {
+        Self {
+            max_versions: env::var("MAX_VERSIONS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionEntry {
+    pub id: String,
+    pub size: u64,
+    pub uploaded_at: u64,
+    pub archived_at: u64,
+    #[serde(default)]
+    pub meta: FileMeta,
+}
+
+fn history_dir(upload_dir: &str, file_id: &str) -> PathBuf {
+    PathBuf::from(upload_dir).join(VERSIONS_DIR).join(file_id)
+}
+
+// Clé du contenu d'une version dans le stockage (la description reste dans history_dir)
+fn version_key(file_id: &str, id: &str) -> String {
+    format!("{}/{}/{}", VERSIONS_DIR, file_id, id)
+}
+
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_digit())
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+// Archive le fichier courant comme version précédente puis applique la limite
+pub fn archive_current(upload_dir: &str, file_id: &str, max_versions: usize) -> std::io::Result<()> {
+    let storage = storage::open(upload_dir);
+    let current = storage
+        .stat(file_id)?
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, file_id.to_string()))?;
+    let dir = history_dir(upload_dir, file_id);
+    fs::create_dir_all(&dir)?;
+
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_nanos()
+        .to_string();
+    let entry = VersionEntry {
+        id: id.clone(),
+        size: current.size,
+        uploaded_at: current.modified,
+        archived_at: unix_secs(SystemTime::now()),
+        meta: meta::load(upload_dir, file_id),
+    };
+
+    storage.rename(file_id, &version_key(file_id, &id))?;
+    let content = serde_json::to_string(&entry).map_err(std::io::Error::other)?;
+    fs::write(dir.join(format!("{}.json", id)), content)?;
+    prune(upload_dir, file_id, max_versions);
+    Ok(())
+}
+
+// Versions précédentes d'un fichier, de la plus récente à la plus ancienne
+pub fn list(upload_dir: &str, file_id: &str) -> Vec<VersionEntry> {
+    if !names::valid_id(file_id) {
+        return Vec::new();
+    }
+    let mut entries: Vec<VersionEntry> = fs::read_dir(history_dir(upload_dir, file_id))
+        .map(|dir| {
+            dir.flatten()
+                .filter(|e| e.path().extension().is_some_and(|ext| ext == "json"))
+                .filter_map(|e| fs::read_to_string(e.path()).ok())
+                .filter_map(|content| serde_json::from_str(&content).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    // Les identifiants (nanosecondes) départagent les versions archivées dans la même seconde
+    entries.sort_by_key(|e| std::cmp::Reverse(e.id.parse::<u128>().unwrap_or(0)));
+    entries
+}
+
+// Rattache l'historique d'un fichier à un nouvel identifiant (restauration
+// depuis la corbeille), sauf si ce dernier a déjà son propre historique
+pub fn move_history(upload_dir: &str, from_id: &str, to_id: &str) {
+    let (from, to) = (history_dir(upload_dir, from_id), history_dir(upload_dir, to_id));
+    if from_id == to_id || !names::valid_id(from_id) || !from.is_dir() || to.exists() {
+        return;
+    }
+    let storage = storage::open(upload_dir);
+    let moved = fs::create_dir_all(&to).and_then(|_| {
+        for entry in list(upload_dir, from_id) {
+            storage.rename(&version_key(from_id, &entry.id), &version_key(to_id, &entry.id))?;
+            let description = format!("{}.json", entry.id);
+            fs::rename(from.join(&description), to.join(&description))?;
+        }
+        fs::remove_dir(&from)
+    });
+    if let Err(e) = moved {
+        warn!(id = from_id, error = %e, "⚠️ Erreur rattachement historique");
+    }
+}
+
+// Supprime tout l'historique d'un fichier supprimé définitivement
+pub fn purge(upload_dir: &str, file_id: &str) {
+    if names::valid_id(file_id) {
+        prune(upload_dir, file_id, 0);
+    }
+}
+
+fn prune(upload_dir: &str, file_id: &str, max_versions: usize) {
+    let dir = history_dir(upload_dir, file_id);
+    let storage = storage::open(upload_dir);
+    for entry in list(upload_dir, file_id).into_iter().skip(max_versions) {
+        let _ = storage.delete(&version_key(file_id, &entry.id));
+        let _ = fs::remove_file(dir.join(format!("{}.json", entry.id)));
+        dedup::release(upload_dir, entry.meta.sha256.as_deref());
+        info!(file = %names::display_name(upload_dir, file_id), version = %entry.id, "🕘 Ancienne version supprimée");
+    }
+    // Ne pas laisser de dossier d'historique vide
+    let _ = fs::remove_dir(&dir);
+    quota::released();
+}
+
+// La version choisie redevient courante, la version courante est archivée
+fn restore_version(upload_dir: &str, file_id: &str, id: &str, max_versions: usize) -> std::io::Result<()> {
+    let dir = history_dir(upload_dir, file_id);
+    let entry_path = dir.join(format!("{}.json", id));
+    let content = fs::read_to_string(&entry_path)?;
+    let entry: VersionEntry = serde_json::from_str(&content).map_err(std::io::Error::other)?;
+
+    // Mettre la version à restaurer de côté avant d'archiver le fichier courant,
+    // sinon la limite de versions pourrait la supprimer
+    let storage = storage::open(upload_dir);
+    let restoring = format!("{}/{}_{}", crate::STAGING_DIR, file_id, id);
+    storage.rename(&version_key(file_id, id), &restoring)?;
+    let restored = fs::remove_file(&entry_path).and_then(|_| {
+        if storage.stat(file_id)?.is_some() {
+            archive_current(upload_dir, file_id, max_versions)?;
+        }
+        storage.rename(&restoring, file_id)
+    });
+    if let Err(e) = restored {
+        // Le staging est vidé au démarrage : la version reprend sa place
+        let rollback = storage
+            .rename(&restoring, &version_key(file_id, id))
+            .and_then(|_| fs::create_dir_all(&dir))
+            .and_then(|_| fs::write(&entry_path, &content));
+        if let Err(rollback) = rollback {
+            error!(file = %names::display_name(upload_dir, file_id), version = id, error = %rollback, "❌ Erreur remise en place de la version");
+        }
+        return Err(e);
+    }
+
+    // Le fichier garde son nom actuel, seuls le contenu et l'expiration changent
+    let mut file_meta = entry.meta;
+    file_meta.name = Some(names::display_name(upload_dir, file_id));
+    meta::save(upload_dir, file_id, &file_meta)
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(serde_json::json!({
+        "error": "Version introuvable"
+    }))
+}
+
+#[get("/versions/{file_id}")]
+async fn history(path: web::Path<String>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let file_id = path.into_inner();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "id": file_id,
+        "file": names::display_name(&config.upload_dir, &file_id),
+        "versions": list(&config.upload_dir, &file_id),
+    })))
+}
+
+#[get("/versions/{file_id}/{id}")]
+async fn download_version(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let (file_id, id) = path.into_inner();
+    if !names::valid_id(&file_id) || !valid_id(&id) {
+        return Ok(not_found());
+    }
+
+    let filename = names::display_name(&config.upload_dir, &file_id);
+    // Empreinte du contenu de cette version, enregistrée lors de son archivage
+    let file_meta = fs::read_to_string(history_dir(&config.upload_dir, &file_id).join(format!("{}.json", id)))
+        .ok()
+        .and_then(|content| serde_json::from_str::<VersionEntry>(&content).ok())
+        .map(|entry| entry.meta)
+        .unwrap_or_default();
+    let mut response = storage::download(&req, &config.upload_dir, &version_key(&file_id, &id), &filename, &file_meta).await?;
+    // Une version archivée ne change plus jamais
+    if response.status().is_success() || response.status() == StatusCode::NOT_MODIFIED {
+        response
+            .headers_mut()
+            .insert(header::CACHE_CONTROL, header::HeaderValue::from_static(cache::IMMUTABLE));
+    }
+    audit::download(&req, &config.upload_dir, &file_id, &filename, &response);
+    Ok(response)
+}
+
+#[post("/versions/{file_id}/{id}/restore")]
+async fn restore(req: HttpRequest, path: web::Path<(String, String)>) -> Result<HttpResponse> {
+    let config = Config::from_env();
+    let (file_id, id) = path.into_inner();
+    if !names::valid_id(&file_id) || !valid_id(&id) {
+        return Ok(not_found());
+    }
+    if !history_dir(&config.upload_dir, &file_id).join(format!("{}.json", id)).exists() {
+        return Ok(not_found());
+    }
+
+    let upload_dir = config.upload_dir.clone();
+    let max_versions = config.versioning.max_versions;
+    let filename = names::display_name(&config.upload_dir, &file_id);
+    let (restored_id, version_id) = (file_id.clone(), id.clone());
+    match web::block(move || restore_version(&upload_dir, &file_id, &id, max_versions)).await? {
+        Ok(_) => {
+            info!(file = %filename, version = %version_id, "🕘 Version restaurée");
+            audit::record(&config.upload_dir, audit::Entry::new(audit::Event::Restore, &access::client_address(&req), &filename)
+                .id(&restored_id)
+                .detail(&format!("version {}", version_id)));
+            Ok(HttpResponse::Found()
+                .insert_header(("Location", "/"))
+                .finish())
+        }
+        Err(e) => {
+            error!(file = %filename, error = %e, "❌ Erreur restauration version");
+            Ok(HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": "Erreur lors de la restauration de la version"
+            })))
+        }
+    }
+}
@@ -0,0 +1,471 @@
+// Webhooks : un POST JSON signé (HMAC-SHA256) vers chaque URL configurée à
+// chaque upload, suppression ou expiration. Les envois partent d'une file en
+// arrière-plan, avec nouvelles tentatives espacées en cas d'échec.
+use std::{
+    env,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        OnceLock,
+    },
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use chacha20poly1305::aead::{rand_core::RngCore, OsRng};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::{error, info, warn};
+
+use crate::{get_uploaded_file, FileInfo};
+
+// Attente avant la première nouvelle tentative, doublée à chaque échec
+const BACKOFF_BASE: Duration = Duration::from_secs(2);
+const BACKOFF_MAX: Duration = Duration::from_secs(600);
+// Au-delà, les envois les plus anciens sont abandonnés
+const MAX_QUEUE: usize = 1000;
+const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Event {
+    Upload,
+    Delete,
+    Expire,
+}
+
+impl Event {
+    const ALL: [Event; 3] = [Event::Upload, Event::Delete, Event::Expire];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Upload => "upload",
+            Self::Delete => "delete",
+            Self::Expire => "expire",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    urls: Vec<String>,
+    events: Vec<Event>,
+    // Clé de signature des envois (None = envois non signés)
+    secret: Option<String>,
+    // Nouvelles tentatives après un premier envoi en échec
+    retries: u32,
+    // Attente avant la première nouvelle tentative
+    backoff: Duration,
+}
+
+impl WebhookConfig {
+    pub fn from_env() -> Self {
+        Self {
+            urls: env::var("WEBHOOK_URL")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+                .map(str::to_string)
+                .collect(),
+            events: env::var("WEBHOOK_EVENTS")
+                .ok()
+                .filter(|v| !v.trim().is_empty() && v.trim() != "all")
+                .map(|v| {
+                    Event::ALL
+                        .into_iter()
+                        .filter(|event| v.split(',').any(|name| name.trim().eq_ignore_ascii_case(event.as_str())))
+                        .collect()
+                })
+                .unwrap_or_else(|| Event::ALL.to_vec()),
+            secret: env::var("WEBHOOK_SECRET").ok().filter(|s| !s.is_empty()),
+            retries: env::var("WEBHOOK_RETRIES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(5),
+            backoff: BACKOFF_BASE,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.urls.is_empty() && !self.events.is_empty()
+    }
+
+    pub fn describe(&self) -> String {
+        if !self.enabled() {
+            return "désactivés".to_string();
+        }
+        let events: Vec<&str> = self.events.iter().map(|e| e.as_str()).collect();
+        format!(
+            "{} URL(s), {}, {}",
+            self.urls.len(),
+            events.join("/"),
+            if self.secret.is_some() { "signés" } else { "non signés" }
+        )
+    }
+}
+
+#[derive(Serialize)]
+struct Payload<'a> {
+    // Identifiant de l'événement, repris dans X-Webhook-Delivery
+    id: &'a str,
+    event: Event,
+    timestamp: u64,
+    // Client à l'origine de l'opération, ou "serveur"
+    actor: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<&'a str>,
+    file: &'a FileInfo,
+}
+
+struct Delivery {
+    url: String,
+    event: Event,
+    id: String,
+    body: String,
+    attempt: u32,
+    // Mise en file de l'événement (inchangée par les nouvelles tentatives)
+    queued_at: Instant,
+    due: Instant,
+}
+
+struct Queue {
+    sender: Sender<Delivery>,
+    config: WebhookConfig,
+}
+
+static QUEUE: OnceLock<Queue> = OnceLock::new();
+// Envois en attente d'une nouvelle tentative
+static PENDING: AtomicUsize = AtomicUsize::new(0);
+
+// Démarre la file d'envoi si des webhooks sont configurés
+pub fn spawn_worker(config: &WebhookConfig) {
+    if !config.enabled() {
+        return;
+    }
+    let (sender, receiver) = mpsc::channel();
+    if QUEUE.set(Queue { sender, config: config.clone() }).is_err() {
+        return;
+    }
+    let config = config.clone();
+    thread::spawn(move || run(receiver, &config));
+}
+
+fn subscribed(event: Event) -> Option<&'static Queue> {
+    QUEUE.get().filter(|queue| queue.config.events.contains(&event))
+}
+
+// Fichier concerné par l'événement, lu seulement si quelqu'un y est abonné
+pub fn lookup(event: Event, upload_dir: &str, id: &str) -> Option<FileInfo> {
+    subscribed(event).and_then(|_| get_uploaded_file(upload_dir, id))
+}
+
+// Met l'événement en file pour chaque URL ; ne bloque jamais la requête
+pub fn notify(event: Event, actor: &str, file: &FileInfo, detail: Option<&str>) {
+    let Some(queue) = subscribed(event) else {
+        return;
+    };
+    for delivery in deliveries(&queue.config, event, actor, file, detail) {
+        let _ = queue.sender.send(delivery);
+    }
+}
+
+fn deliveries(config: &WebhookConfig, event: Event, actor: &str, file: &FileInfo, detail: Option<&str>) -> Vec<Delivery> {
+    let mut bytes = [0u8; 8];
+    OsRng.fill_bytes(&mut bytes);
+    let id = hex::encode(bytes);
+    let payload = Payload {
+        id: &id,
+        event,
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        actor,
+        detail,
+        file,
+    };
+    let body = match serde_json::to_string(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            error!(error = %e, "❌ Erreur sérialisation webhook");
+            return Vec::new();
+        }
+    };
+    let now = Instant::now();
+    config
+        .urls
+        .iter()
+        .map(|url| Delivery {
+            url: url.clone(),
+            event,
+            id: id.clone(),
+            body: body.clone(),
+            attempt: 0,
+            queued_at: now,
+            due: now,
+        })
+        .collect()
+}
+
+pub fn pending() -> usize {
+    PENDING.load(Ordering::Relaxed)
+}
+
+fn run(receiver: Receiver<Delivery>, config: &WebhookConfig) {
+    let agent = ureq::AgentBuilder::new()
+        .timeout_connect(Duration::from_secs(10))
+        .timeout(Duration::from_secs(30))
+        .build();
+    let mut queue: Vec<Delivery> = Vec::new();
+    loop {
+        let next = queue.iter().map(|d| d.due).min();
+        let received = match next {
+            Some(due) => receiver.recv_timeout(due.saturating_duration_since(Instant::now())),
+            None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(delivery) => queue.push(delivery),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+        for dropped in drop_oldest(&mut queue, MAX_QUEUE) {
+            warn!(url = %dropped.url, event = dropped.event.as_str(), delivery = %dropped.id, "⚠️ File des webhooks pleine, envoi abandonné");
+        }
+
+        let now = Instant::now();
+        let (due, later): (Vec<_>, Vec<_>) = queue.into_iter().partition(|d| d.due <= now);
+        queue = later;
+        for delivery in due {
+            if let Some(retry) = deliver(&agent, config, delivery) {
+                queue.push(retry);
+            }
+        }
+        PENDING.store(queue.len(), Ordering::Relaxed);
+    }
+}
+
+// File trop longue : les événements mis en file les premiers sont abandonnés,
+// pas les nouveaux (un destinataire en panne ne fait qu'accumuler des retards)
+fn drop_oldest(queue: &mut Vec<Delivery>, max: usize) -> Vec<Delivery> {
+    if queue.len() <= max {
+        return Vec::new();
+    }
+    queue.sort_by_key(|d| d.queued_at);
+    queue.drain(..queue.len() - max).collect()
+}
+
+// Envoie une fois ; renvoie l'envoi reprogrammé s'il faut réessayer
+fn deliver(agent: &ureq::Agent, config: &WebhookConfig, mut delivery: Delivery) -> Option<Delivery> {
+    let mut request = agent
+        .post(&delivery.url)
+        .set("Content-Type", "application/json")
+        .set("User-Agent", concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")))
+        .set("X-Webhook-Event", delivery.event.as_str())
+        .set("X-Webhook-Delivery", &delivery.id);
+    if let Some(secret) = &config.secret {
+        request = request.set(SIGNATURE_HEADER, &format!("sha256={}", sign(secret, &delivery.body)));
+    }
+
+    let error = match request.send_string(&delivery.body) {
+        Ok(response) => {
+            info!(url = %delivery.url, event = delivery.event.as_str(), delivery = %delivery.id, status = response.status(), "🪝 Webhook envoyé");
+            return None;
+        }
+        // Refus définitif du destinataire : inutile de réessayer
+        Err(ureq::Error::Status(status, _)) if !matches!(status, 408 | 429 | 500..) => {
+            error!(url = %delivery.url, event = delivery.event.as_str(), delivery = %delivery.id, status, "❌ Webhook refusé");
+            return None;
+        }
+        Err(ureq::Error::Status(status, _)) => format!("statut {}", status),
+        Err(ureq::Error::Transport(e)) => e.to_string(),
+    };
+
+    if delivery.attempt >= config.retries {
+        error!(url = %delivery.url, event = delivery.event.as_str(), delivery = %delivery.id, attempts = delivery.attempt + 1, error = %error, "❌ Webhook abandonné");
+        return None;
+    }
+    let delay = config.backoff.saturating_mul(1 << delivery.attempt.min(16)).min(BACKOFF_MAX);
+    warn!(
+        url = %delivery.url,
+        event = delivery.event.as_str(),
+        delivery = %delivery.id,
+        error = %error,
+        retry_in_s = delay.as_secs(),
+        "⚠️ Échec webhook, nouvelle tentative programmée"
+    );
+    delivery.attempt += 1;
+    delivery.due = Instant::now() + delay;
+    Some(delivery)
+}
+
+// Signature du corps exact envoyé, à vérifier par le destinataire
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepte toutes les tailles de clé");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeMap,
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+
+    const SECRET: &str = "s3cret";
+
+    struct Received {
+        path: String,
+        headers: BTreeMap<String, String>,
+        body: String,
+        at: Instant,
+    }
+
+    // Destinataire local : /instable répond 500 puis 204, /refus répond toujours 404
+    fn spawn_receiver() -> (String, Arc<Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::<Received>::new()));
+        let log = received.clone();
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(&stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap_or_default().to_string();
+                let mut headers = BTreeMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(':') else { break };
+                    headers.insert(name.to_lowercase(), value.trim().to_string());
+                }
+                let length = headers.get("content-length").and_then(|v| v.parse().ok()).unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut log = log.lock().unwrap();
+                let attempts = log.iter().filter(|r| r.path == path).count();
+                let status = match path.as_str() {
+                    "/instable" if attempts == 0 => "500 Internal Server Error",
+                    "/instable" => "204 No Content",
+                    _ => "404 Not Found",
+                };
+                log.push(Received { path, headers, body: String::from_utf8(body).unwrap(), at: Instant::now() });
+                drop(log);
+                let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            }
+        });
+        (base, received)
+    }
+
+    fn file() -> FileInfo {
+        FileInfo {
+            id: "1792358163989314408".to_string(),
+            name: "rapport.pdf".to_string(),
+            size: 48213,
+            uploaded_at: 1_700_000_000,
+            file_type: "pdf".to_string(),
+            expires_at: None,
+            uploader: Some("192.168.1.20".to_string()),
+            sha256: Some("ab".repeat(32)),
+            blake3: None,
+            verified_at: None,
+            corrupt: false,
+            mime: Some("application/pdf".to_string()),
+            description: None,
+            tags: vec!["compta".to_string()],
+        }
+    }
+
+    fn config(urls: Vec<String>) -> WebhookConfig {
+        WebhookConfig {
+            urls,
+            events: Event::ALL.to_vec(),
+            secret: Some(SECRET.to_string()),
+            retries: 3,
+            backoff: Duration::from_millis(200),
+        }
+    }
+
+    #[test]
+    fn delivers_signed_payload_and_retries_on_server_error() {
+        let (base, received) = spawn_receiver();
+        let config = config(vec![format!("{}/instable", base), format!("{}/refus", base)]);
+        let (sender, receiver) = mpsc::channel();
+        let worker_config = config.clone();
+        thread::spawn(move || run(receiver, &worker_config));
+        for delivery in deliveries(&config, Event::Upload, "192.168.1.20", &file(), Some("analyse OK")) {
+            sender.send(delivery).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.lock().unwrap().iter().filter(|r| r.path == "/instable").count() < 2 {
+            assert!(Instant::now() < deadline, "nouvelle tentative jamais reçue");
+            thread::sleep(Duration::from_millis(20));
+        }
+        // Laisse passer une éventuelle tentative de trop
+        thread::sleep(Duration::from_millis(600));
+        let received = received.lock().unwrap();
+
+        let attempts: Vec<&Received> = received.iter().filter(|r| r.path == "/instable").collect();
+        assert_eq!(attempts.len(), 2, "204 après le 500 : pas d'autre tentative");
+        assert!(attempts[1].at - attempts[0].at >= Duration::from_millis(200), "attente avant la nouvelle tentative");
+        // 404 : refus définitif, une seule tentative
+        assert_eq!(received.iter().filter(|r| r.path == "/refus").count(), 1);
+
+        for request in &attempts {
+            assert_eq!(request.body, attempts[0].body, "même contenu à chaque tentative");
+            let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes()).unwrap();
+            mac.update(request.body.as_bytes());
+            let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+            assert_eq!(request.headers.get("x-webhook-signature"), Some(&expected));
+            assert_eq!(request.headers.get("x-webhook-event").map(String::as_str), Some("upload"));
+            assert_eq!(request.headers.get("content-type").map(String::as_str), Some("application/json"));
+        }
+
+        let payload: serde_json::Value = serde_json::from_str(&attempts[0].body).unwrap();
+        assert_eq!(attempts[0].headers.get("x-webhook-delivery").map(String::as_str), payload["id"].as_str());
+        assert_eq!(payload["event"], "upload");
+        assert_eq!(payload["actor"], "192.168.1.20");
+        assert_eq!(payload["detail"], "analyse OK");
+        assert!(payload["timestamp"].as_u64().unwrap() > 1_700_000_000);
+        assert_eq!(payload["file"]["id"], "1792358163989314408");
+        assert_eq!(payload["file"]["name"], "rapport.pdf");
+        assert_eq!(payload["file"]["size"], 48213);
+        assert_eq!(payload["file"]["mime"], "application/pdf");
+        assert_eq!(payload["file"]["tags"], serde_json::json!(["compta"]));
+        drop(sender);
+    }
+
+    #[test]
+    fn full_queue_drops_oldest_events() {
+        let config = config(vec!["http://127.0.0.1:9/".to_string()]);
+        let mut queue = Vec::new();
+        // Anciens événements en attente de nouvelle tentative, échéance lointaine
+        for _ in 0..3 {
+            let mut delivery = deliveries(&config, Event::Delete, "serveur", &file(), None).remove(0);
+            delivery.attempt = 4;
+            delivery.due = Instant::now() + Duration::from_secs(300);
+            queue.push(delivery);
+            thread::sleep(Duration::from_millis(2));
+        }
+        let old: Vec<String> = queue.iter().map(|d| d.id.clone()).collect();
+        // Nouveaux événements, à envoyer tout de suite
+        for _ in 0..2 {
+            queue.extend(deliveries(&config, Event::Upload, "10.0.0.1", &file(), None));
+        }
+        let fresh: Vec<String> = queue[3..].iter().map(|d| d.id.clone()).collect();
+
+        let dropped: Vec<String> = drop_oldest(&mut queue, 3).into_iter().map(|d| d.id).collect();
+        assert_eq!(dropped, old[..2]);
+        let kept: Vec<String> = queue.iter().map(|d| d.id.clone()).collect();
+        assert_eq!(kept, [vec![old[2].clone()], fresh].concat());
+        assert!(drop_oldest(&mut queue, 3).is_empty());
+    }
+}